@@ -0,0 +1,104 @@
+use crate::derivation::Derivation;
+
+pub struct Page {
+    // これより幅の広い導出は前提を横に並べず縦に積む
+    pub width: usize,
+    // 横線を ─ でなく - で書く
+    pub ascii: bool
+}
+
+impl Default for Page {
+    fn default() -> Page {
+        Page { width: 80, ascii: false }
+    }
+}
+
+// 教科書の形で書く
+//
+//   前提1   前提2
+//   ───────────── 規則名
+//       結論
+//
+// 前提は下を揃えて横に並べ, 横線は前提と結論の広い方に合わせる
+// 横に並べると page.width を超えるときは, 前提を縦に積む
+pub fn bar(d: &Derivation, page: &Page) -> String {
+    block(d, page, page.width).lines.iter().map(|line| format!("{}\n", line.trim_end())).collect()
+}
+
+// 四角く切り出した文字の塊
+struct Block {
+    lines: Vec<String>,
+    width: usize
+}
+
+impl Block {
+    fn text(s: &str) -> Block {
+        Block { lines: vec![s.to_string()], width: s.chars().count() }
+    }
+
+    // 幅を揃えるために右を空白で埋めた行
+    fn line(&self, i: usize) -> String {
+        let line = self.lines.get(i).map_or("", |l| l.as_str());
+        format!("{}{}", line, " ".repeat(self.width - line.chars().count()))
+    }
+}
+
+// width はこの導出に使える幅 (外側の規則名と字下げの分を page.width から引いたもの)
+// 前提には規則名の分を, 縦に積むかもしれないときは字下げの分も引いて渡す
+fn block(d: &Derivation, page: &Page, width: usize) -> Block {
+    let conclusion = d.judgment.trim();
+    let inner = width.saturating_sub(1 + d.rule.chars().count());
+    let above = if d.hole {
+        Some(Block::text("?"))
+    } else if d.premises.is_empty() {
+        None
+    } else {
+        let room = if d.premises.len() > 1 { inner.saturating_sub(2) } else { inner };
+        let premises = d.premises.iter().map(|p| block(p, page, room)).collect::<Vec<_>>();
+        let side = beside(&premises);
+        if side.width > inner && premises.len() > 1 {
+            Some(stack(&premises))
+        } else {
+            Some(side)
+        }
+    };
+    let width = above.as_ref().map_or(0, |a| a.width).max(conclusion.chars().count());
+    let mut lines = vec![];
+    if let Some(above) = &above {
+        for i in 0..above.lines.len() {
+            lines.push(center(&above.line(i), above.width, width));
+        }
+    }
+    lines.push(format!("{} {}", (if page.ascii { "-" } else { "─" }).repeat(width), d.rule));
+    lines.push(center(conclusion, conclusion.chars().count(), width));
+    Block { lines, width: width + 1 + d.rule.chars().count() }
+}
+
+fn center(s: &str, len: usize, width: usize) -> String {
+    format!("{}{}", " ".repeat((width - len) / 2), s)
+}
+
+// 下を揃えて三つの空白を挟んで並べる
+fn beside(blocks: &[Block]) -> Block {
+    let height = blocks.iter().map(|b| b.lines.len()).max().unwrap_or(0);
+    let lines = (0..height).map(|i| {
+        blocks.iter().map(|b| match (i + b.lines.len()).checked_sub(height) {
+            Some(j) => b.line(j),
+            None => " ".repeat(b.width)
+        }).collect::<Vec<_>>().join("   ")
+    }).collect();
+    let width = blocks.iter().map(|b| b.width).sum::<usize>() + 3 * (blocks.len().max(1) - 1);
+    Block { lines, width }
+}
+
+// 空行で区切り, 二つ字下げして縦に積む (どの横線の前提かが字下げで分かる)
+fn stack(blocks: &[Block]) -> Block {
+    let mut lines = vec![];
+    for (i, b) in blocks.iter().enumerate() {
+        if i != 0 {
+            lines.push(String::new());
+        }
+        lines.extend(b.lines.iter().map(|l| format!("  {}", l)));
+    }
+    Block { lines, width: 2 + blocks.iter().map(|b| b.width).max().unwrap_or(0) }
+}
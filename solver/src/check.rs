@@ -0,0 +1,861 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::derivation::Derivation;
+use crate::expr::{Expr, Prim, Value, Env, EnvVar, Form};
+use crate::nat::{NatExp, NatJudgment, parse_nat_judgment};
+use crate::parser::{parse, parse_value, parse_typing};
+use crate::solver::{System, ml1_form};
+use crate::tokenizer::{tokenize, Token};
+use crate::types::{Type, Scheme, TypeEnv, TypeForm};
+use crate::typing::{type_vars, substitute, match_clauses};
+
+// 書かれた導出を根から順に調べ, 最初に見つかった誤りを返す
+// 規則名があるか, 結論がその規則の形か, 前提が正しい判断か, 算術の条件が成り立つかを調べる
+
+// path は根からたどった前提の番号 (1 から数える)
+#[derive(Debug)]
+pub struct CheckError {
+    pub path: Vec<usize>,
+    pub line: usize,
+    pub judgment: String,
+    pub rule: String,
+    pub message: String
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, {}: {} by {}\n  {}", self.line, path_string(&self.path), self.judgment, self.rule, self.message)
+    }
+}
+
+impl std::error::Error for CheckError {}
+
+// root, root.2.1, ...
+pub fn path_string(path: &[usize]) -> String {
+    let mut s = String::from("root");
+    for i in path {
+        s.push_str(&format!(".{}", i));
+    }
+    s
+}
+
+pub fn check(d: &Derivation, sys: System) -> Result<(), CheckError> {
+    no_holes(d, &mut vec![])?;
+    // 式や値は字句を借りるので, 先に導出全体を字句に分けておく
+    match sys {
+        System::EvalML1 => check_node(d, &Tokenized::new(d, EvalTokens::new), &mut vec![], &check_eval_ml1),
+        System::EvalML3 => check_node(d, &Tokenized::new(d, EvalTokens::new), &mut vec![], &check_eval),
+        System::Nat | System::CompareNat1 | System::CompareNat2 | System::CompareNat3
+            | System::EvalNatExp | System::ReduceNatExp =>
+            check_node(d, &Tokenized::new(d, parse_nat_judgment), &mut vec![], &|d, t| check_nat(d, t, sys)),
+        System::TypingML4 | System::PolyTypingML4 =>
+            check_node(d, &Tokenized::new(d, TypingTokens::new), &mut vec![], &|d, t| check_typing(d, t, sys)),
+        _ => Err(error_at(d, &[], format!("checker does not support {:?}", sys)))
+    }
+}
+
+// 書きかけの導出は --complete で埋めてから調べる
+fn no_holes(d: &Derivation, path: &mut Vec<usize>) -> Result<(), CheckError> {
+    if d.hole || d.rule == "?" || d.judgment.contains('?') {
+        return Err(error_at(d, path, "derivation has holes (`?`); use --complete to fill them".to_string()))
+    }
+    for (i, premise) in d.premises.iter().enumerate() {
+        path.push(i + 1);
+        no_holes(premise, path)?;
+        path.pop();
+    }
+    Ok(())
+}
+
+fn check_node<T, F>(d: &Derivation, t: &Tokenized<T>, path: &mut Vec<usize>, check_rule: &F) -> Result<(), CheckError>
+where
+    F: Fn(&Derivation, &Tokenized<T>) -> Result<(), String>
+{
+    check_rule(d, t).map_err(|message| error_at(d, path, message))?;
+
+    for (i, (premise, t)) in d.premises.iter().zip(&t.premises).enumerate() {
+        path.push(i + 1);
+        check_node(premise, t, path, check_rule)?;
+        path.pop();
+    }
+
+    Ok(())
+}
+
+fn error_at(d: &Derivation, path: &[usize], message: String) -> CheckError {
+    CheckError {
+        path: path.to_vec(),
+        line: d.line,
+        judgment: d.judgment.clone(),
+        rule: d.rule.clone(),
+        message
+    }
+}
+
+// ε |- e evalto v を環境, 式, 値の字句に分けたもの
+// EvalML1 の判断には環境がない
+// EvalML1Err の evalto error は値を None として読む
+struct EvalTokens {
+    env: Vec<Token>,
+    expr: Vec<Token>,
+    value: Vec<Token>
+}
+
+impl EvalTokens {
+    fn new(judgment: &str) -> Result<EvalTokens, String> {
+        let (form, value) = judgment.split_once(" evalto ")
+            .ok_or_else(|| format!("expected `ε |- e evalto v`, found `{}`", judgment))?;
+        let (env, expr) = form.split_once("|-").unwrap_or(("", form));
+        let tokens = |s: &str| tokenize(s.as_bytes()).map_err(|e| e.to_string());
+
+        Ok(EvalTokens { env: tokens(env)?, expr: tokens(expr)?, value: tokens(value)? })
+    }
+
+    fn parse(&self) -> Result<(Form<'_>, Option<Value<'_>>), String> {
+        let form = parse(&self.env, &self.expr).map_err(|e| e.to_string())?;
+        let value = match self.value.as_slice() {
+            [Token::Var(error)] if error == "error" => None,
+            tokens => Some(parse_value(tokens).map_err(|e| e.to_string())?)
+        };
+        Ok((form, value))
+    }
+}
+
+// Γ |- e : τ を環境と残りの字句に分けたもの
+struct TypingTokens {
+    env: Vec<Token>,
+    expr: Vec<Token>
+}
+
+impl TypingTokens {
+    fn new(judgment: &str) -> Result<TypingTokens, String> {
+        let (env, expr) = judgment.split_once("|-")
+            .ok_or_else(|| format!("expected `Γ |- e : τ`, found `{}`", judgment))?;
+        let tokens = |s: &str| tokenize(s.as_bytes()).map_err(|e| e.to_string());
+
+        Ok(TypingTokens { env: tokens(env)?, expr: tokens(expr)? })
+    }
+
+    fn parse(&self) -> Result<(TypeEnv<'_>, Expr<'_>, Type), String> {
+        match parse_typing(&self.env, &self.expr).map_err(|e| e.to_string())? {
+            TypeForm(env, expr, Some(t)) => Ok((env, expr, t)),
+            TypeForm(_, expr, None) => Err(format!("the type of `{}` is missing", expr))
+        }
+    }
+}
+
+// 導出と同じ形の木
+// 判断が読めなければ (evalto の木の B-Plus など) そのときのエラーを持つ
+struct Tokenized<T> {
+    judgment: Result<T, String>,
+    premises: Vec<Tokenized<T>>
+}
+
+impl<T> Tokenized<T> {
+    fn new(d: &Derivation, read: fn(&str) -> Result<T, String>) -> Tokenized<T> {
+        Tokenized {
+            judgment: read(&d.judgment),
+            premises: d.premises.iter().map(|p| Tokenized::new(p, read)).collect()
+        }
+    }
+
+    fn judgment(&self) -> Result<&T, String> {
+        self.judgment.as_ref().map_err(|e| e.clone())
+    }
+}
+
+impl Tokenized<EvalTokens> {
+    fn parse(&self) -> Result<(Form<'_>, Option<Value<'_>>), String> {
+        self.judgment()?.parse()
+    }
+}
+
+impl Tokenized<TypingTokens> {
+    fn parse(&self) -> Result<(TypeEnv<'_>, Expr<'_>, Type), String> {
+        self.judgment()?.parse()
+    }
+}
+
+// i1 plus i2 is i3, i1 less than i2 is b
+struct Arith {
+    op: String,
+    left: isize,
+    right: isize,
+    result: String
+}
+
+fn arith(judgment: &str) -> Result<Arith, String> {
+    let words = judgment.split_whitespace().collect::<Vec<_>>();
+    let (left, op, right, result) = match words.as_slice() {
+        [l, "less", "than", r, "is", v] => (l, "less than".to_string(), r, v),
+        [l, op, r, "is", v] => (l, op.to_string(), r, v),
+        _ => return Err(format!("expected `i1 op i2 is i3`, found `{}`", judgment))
+    };
+    let int = |s: &str| s.parse::<isize>().map_err(|_| format!("expected integer, found {}", s));
+
+    Ok(Arith { op, left: int(left)?, right: int(right)?, result: result.to_string() })
+}
+
+// 結論の判断 (先頭の |- の前の空白は書かないことが多い)
+fn judge(env: &Env, expr: &Expr) -> String {
+    env.form().trim_start().to_string() + &format!(" {}", expr)
+}
+
+// EvalML1 の式は変数や let を含まない
+fn check_eval_ml1(d: &Derivation, t: &Tokenized<EvalTokens>) -> Result<(), String> {
+    if !d.rule.starts_with("B-") {
+        ml1_form(&t.parse()?.0).map_err(|e| e.to_string())?;
+    }
+    check_eval(d, t)
+}
+
+fn check_eval(d: &Derivation, t: &Tokenized<EvalTokens>) -> Result<(), String> {
+    if d.rule.starts_with("B-") {
+        return check_arith(d)
+    }
+    let (Form(env, expr), v) = t.parse()?;
+    let ps = &t.premises;
+    let rules = d.premises.iter().map(|p| p.rule.as_str()).collect::<Vec<_>>();
+    let judgments = d.premises.iter().map(|p| p.judgment.as_str()).collect::<Vec<_>>();
+
+    let expected = match v {
+        Some(_) => expected_rules(&env, &expr),
+        None => error_rules(&expr)
+    };
+    if !RULES.contains(&d.rule.as_str()) && !ERROR_RULES.contains(&d.rule.as_str()) {
+        if expected.is_empty() {
+            return Err(format!("unknown rule {}; no rule applies to `{}`", d.rule, expr))
+        }
+        return Err(format!("unknown rule {}; expected {}", d.rule, expected.join(" or ")))
+    }
+    if !expected.contains(&d.rule.as_str()) {
+        return Err(rule_mismatch(&d.rule, &env, &expr, &expected))
+    }
+    let v = match v {
+        Some(v) => v,
+        None => return check_error(d, ps, &env, &expr)
+    };
+    premise_count(d, match d.rule.as_str() {
+        "E-Int" | "E-Bool" | "E-Var1" | "E-Fun" => 0,
+        "E-Var2" | "E-LetRec" => 1,
+        "E-IfT" | "E-IfF" | "E-Let" => 2,
+        _ => 3
+    })?;
+
+    match (d.rule.as_str(), &expr) {
+        ("E-Int" | "E-Bool", _) => match expr.to_value() {
+            Some(v1) => same_value(&v1, &v),
+            None => Err(format!("{} applies only to a literal, found `{}`", d.rule, expr))
+        },
+        ("E-Plus" | "E-Minus" | "E-Times" | "E-Lt", Expr::Prim(p)) => {
+            let (l, r) = operands(p);
+            let v1 = premise_value(ps, 0, &env, l)?;
+            let v2 = premise_value(ps, 1, &env, r)?;
+            let a = arith(judgments[2]).map_err(|e| format!("premise 3: {}", e))?;
+            let b_rule = format!("B-{}", &d.rule[2..]);
+            if rules[2] != b_rule {
+                return Err(format!("premise 3 must be derived by {}, found {}", b_rule, rules[2]))
+            }
+            if a.left.to_string() != v1.to_string() || a.right.to_string() != v2.to_string() {
+                return Err(format!("premise 3: expected `{} {} {} is ...`, found `{}`", v1, a.op, v2, judgments[2]))
+            }
+            if a.result != v.to_string() {
+                return Err(format!("the result of premise 3 is {}, but the conclusion says {}", a.result, v))
+            }
+            Ok(())
+        }
+        ("E-IfT" | "E-IfF", Expr::IfThenElse(cond, then, els)) => {
+            let (b, branch) = if d.rule == "E-IfT" { (true, then) } else { (false, els) };
+            let c = premise_value(ps, 0, &env, cond)?;
+            if c.to_string() != b.to_string() {
+                return Err(format!("{} needs the condition to be {}, but premise 1 says {}", d.rule, b, c))
+            }
+            let v1 = premise_value(ps, 1, &env, branch)?;
+            same_value(&v1, &v)
+        }
+        ("E-Var1", Expr::Ident(x)) => match env.0[0].1.to_value() {
+            Some(v1) => same_value(&v1, &v),
+            None => Err(format!("{} is not bound to a value", x))
+        },
+        ("E-Var2", Expr::Ident(_)) => {
+            let rest = Env(env.0[1..].to_vec());
+            let v1 = premise_value(ps, 0, &rest, &expr)?;
+            same_value(&v1, &v)
+        }
+        ("E-Let", Expr::Let(x, e1, e2)) => {
+            let v1 = premise_value(ps, 0, &env, e1)?;
+            let v2 = premise_value(ps, 1, &bind(&env, x, v1), e2)?;
+            same_value(&v2, &v)
+        }
+        ("E-Fun", Expr::Fun(x, body)) => same_value(&Value::Closure(env.clone(), x, body.clone()), &v),
+        ("E-App" | "E-AppRec", Expr::App(e1, e2)) => {
+            let closure = premise_value(ps, 0, &env, e1)?;
+            let v2 = premise_value(ps, 1, &env, e2)?;
+            let (new_env, body) = match (d.rule.as_str(), &closure) {
+                ("E-App", Value::Closure(env2, x, body)) => (bind(env2, x, v2), body),
+                ("E-AppRec", Value::RecClosure(env2, f, x, body)) => (bind(&bind(env2, f, closure.clone()), x, v2), body),
+                ("E-App", Value::RecClosure(_, _, _, _)) =>
+                    return Err(format!("premise 1 evaluates to a recursive closure {}; expected E-AppRec", closure)),
+                ("E-AppRec", Value::Closure(_, _, _)) =>
+                    return Err(format!("premise 1 evaluates to a non-recursive closure {}; expected E-App", closure)),
+                _ => return Err(format!("premise 1 must evaluate to a closure, found {}", closure))
+            };
+            let v3 = premise_value(ps, 2, &new_env, body)?;
+            same_value(&v3, &v)
+        }
+        ("E-LetRec", Expr::LetRec(f, x, e1, e2)) => {
+            let closure = Value::RecClosure(env.clone(), f, x, e1.clone());
+            let v1 = premise_value(ps, 0, &bind(&env, f, closure), e2)?;
+            same_value(&v1, &v)
+        }
+        _ => unreachable!("internal: rule and expression are checked by expected_rules")
+    }
+}
+
+// EvalML1Err
+// 二項演算は E-PlusBoolL のように演算と左右と誤りの種類を組み合わせた名前
+// 変数と let の誤りは solver の規則 (E-VarErr, E-LetError1 など) に合わせる
+fn check_error<'a>(d: &Derivation, ps: &'a [Tokenized<EvalTokens>], env: &Env<'a>, expr: &Expr<'a>) -> Result<(), String> {
+    let rule = d.rule.as_str();
+    premise_count(d, match rule {
+        "E-VarErr" => 0,
+        "E-IfTError" | "E-IfFError" | "E-LetError2" => 2,
+        _ => 1
+    })?;
+    // 前提が bool か int か error かを確かめる
+    let expect = |i: usize, e: &Expr<'a>, kind: &str| -> Result<(), String> {
+        let v = premise_outcome(ps, i, env, e)?;
+        let ok = matches!((kind, &v),
+            ("bool", Some(Value::Bool(_))) | ("int", Some(Value::Int(_))) | ("error", None)
+            | ("true", Some(Value::Bool(true))) | ("false", Some(Value::Bool(false))));
+        if !ok {
+            let found = v.map_or("error".to_string(), |v| v.to_string());
+            return Err(format!("{} needs premise {} to evaluate to {}, found {}", rule, i + 1, kind, found))
+        }
+        Ok(())
+    };
+    match expr {
+        Expr::Prim(p) => {
+            let (l, r) = operands(p);
+            match &rule[rule.len() - 5..] {
+                "BoolL" => expect(0, l, "bool"),
+                "BoolR" => expect(0, r, "bool"),
+                "rrorL" => expect(0, l, "error"),
+                _ => expect(0, r, "error")
+            }
+        }
+        Expr::IfThenElse(cond, then, els) => match rule {
+            "E-IfInt" => expect(0, cond, "int"),
+            "E-IfError" => expect(0, cond, "error"),
+            "E-IfTError" => {
+                expect(0, cond, "true")?;
+                expect(1, then, "error")
+            }
+            _ => {
+                expect(0, cond, "false")?;
+                expect(1, els, "error")
+            }
+        },
+        Expr::Ident(x) => match env.0.iter().find(|EnvVar(y, _)| y == x) {
+            Some(_) => Err(format!("E-VarErr applied but {} is bound", x)),
+            None => Ok(())
+        },
+        Expr::Let(x, e1, e2) => match rule {
+            "E-LetError1" => expect(0, e1, "error"),
+            _ => {
+                let v1 = premise_value(ps, 0, env, e1)?;
+                expect_error(ps, 1, &bind(env, x, v1), e2, rule)
+            }
+        },
+        _ => unreachable!("internal: rule and expression are checked by error_rules")
+    }
+}
+
+const RULES: &[&str] = &[
+    "E-Int", "E-Bool", "E-IfT", "E-IfF", "E-Plus", "E-Minus", "E-Times", "E-Lt",
+    "E-Var1", "E-Var2", "E-Let", "E-Fun", "E-App", "E-LetRec", "E-AppRec"
+];
+
+const ERROR_RULES: &[&str] = &[
+    "E-VarErr", "E-LetError1", "E-LetError2",
+    "E-PlusBoolL", "E-PlusBoolR", "E-PlusErrorL", "E-PlusErrorR",
+    "E-MinusBoolL", "E-MinusBoolR", "E-MinusErrorL", "E-MinusErrorR",
+    "E-TimesBoolL", "E-TimesBoolR", "E-TimesErrorL", "E-TimesErrorR",
+    "E-LtBoolL", "E-LtBoolR", "E-LtErrorL", "E-LtErrorR",
+    "E-IfInt", "E-IfError", "E-IfTError", "E-IfFError"
+];
+
+// evalto error の結論に使える規則
+fn error_rules(expr: &Expr) -> Vec<&'static str> {
+    let op = match expr {
+        Expr::Prim(Prim::Add(_, _)) => "E-Plus",
+        Expr::Prim(Prim::Sub(_, _)) => "E-Minus",
+        Expr::Prim(Prim::Mul(_, _)) => "E-Times",
+        Expr::Prim(Prim::LessThan(_, _)) => "E-Lt",
+        Expr::IfThenElse(_, _, _) => "E-If",
+        Expr::Ident(_) => "E-VarErr",
+        Expr::Let(_, _, _) => "E-LetError",
+        _ => return vec![]
+    };
+    ERROR_RULES.iter().copied().filter(|r| r.starts_with(op)).collect()
+}
+
+// 結論の式の形から使える規則
+fn expected_rules(env: &Env, expr: &Expr) -> Vec<&'static str> {
+    match expr {
+        Expr::Value(Value::Bool(_)) => vec!["E-Bool"],
+        Expr::Value(_) => vec!["E-Int"],
+        Expr::Prim(Prim::Add(_, _)) => vec!["E-Plus"],
+        Expr::Prim(Prim::Sub(_, _)) => vec!["E-Minus"],
+        Expr::Prim(Prim::Mul(_, _)) => vec!["E-Times"],
+        Expr::Prim(Prim::LessThan(_, _)) => vec!["E-Lt"],
+        Expr::IfThenElse(_, _, _) => vec!["E-IfT", "E-IfF"],
+        // E-Var1 は一番最近の束縛, E-Var2 はそれより前の束縛
+        Expr::Ident(x) => match env.0.first() {
+            Some(EnvVar(y, _)) if y == x => vec!["E-Var1"],
+            Some(_) => vec!["E-Var2"],
+            None => vec![]
+        },
+        Expr::Let(_, _, _) => vec!["E-Let"],
+        Expr::Fun(_, _) => vec!["E-Fun"],
+        Expr::App(_, _) => vec!["E-App", "E-AppRec"],
+        Expr::LetRec(_, _, _, _) => vec!["E-LetRec"],
+        _ => vec![]
+    }
+}
+
+fn rule_mismatch(rule: &str, env: &Env, expr: &Expr, expected: &[&str]) -> String {
+    match (rule, expr, env.0.first()) {
+        ("E-Var2", Expr::Ident(x), Some(_)) =>
+            format!("E-Var2 applied but {} is the most recent binding; expected E-Var1", x),
+        ("E-Var1", Expr::Ident(x), Some(EnvVar(y, _))) =>
+            format!("E-Var1 applied but the most recent binding is {}, not {}; expected E-Var2", y, x),
+        (_, Expr::Ident(x), None) => format!("{} is not bound", x),
+        _ if expected.is_empty() => format!("{} does not apply to `{}`; no rule derives it", rule, expr),
+        _ => format!("{} does not apply to `{}`; expected {}", rule, expr, expected.join(" or "))
+    }
+}
+
+// 規則名, 前提の数, 結論の形
+const NAT_RULES: &[(&str, usize, &str)] = &[
+    ("P-Zero", 0, "Z plus n is n"),
+    ("P-Succ", 1, "S(n1) plus n2 is S(n3)"),
+    ("T-Zero", 0, "Z times n is Z"),
+    ("T-Succ", 2, "S(n1) times n2 is n4"),
+    ("L-Succ", 0, "n is less than S(n)"),
+    ("L-Trans", 2, "n1 is less than n3"),
+    ("L-Zero", 0, "Z is less than S(n)"),
+    ("L-SuccSucc", 1, "S(n1) is less than S(n2)"),
+    ("L-SuccR", 1, "n1 is less than S(n2)"),
+    ("E-Const", 0, "n evalto n"),
+    ("E-Plus", 3, "e1 + e2 evalto n"),
+    ("E-Times", 3, "e1 * e2 evalto n"),
+    ("R-Plus", 1, "n1 + n2 ---> n3"),
+    ("R-Times", 1, "n1 * n2 ---> n3"),
+    ("R-PlusL", 1, "e1 + e2 ---> e1' + e2"),
+    ("R-PlusR", 1, "e1 + e2 ---> e1 + e2'"),
+    ("R-TimesL", 1, "e1 * e2 ---> e1' * e2"),
+    ("R-TimesR", 1, "e1 * e2 ---> e1 * e2'"),
+    ("DR-Plus", 1, "n1 + n2 -d-> n3"),
+    ("DR-Times", 1, "n1 * n2 -d-> n3"),
+    ("DR-PlusL", 1, "e1 + e2 -d-> e1' + e2"),
+    ("DR-PlusR", 1, "n1 + e2 -d-> n1 + e2'"),
+    ("DR-TimesL", 1, "e1 * e2 -d-> e1' * e2"),
+    ("DR-TimesR", 1, "n1 * e2 -d-> n1 * e2'"),
+    ("MR-Zero", 0, "e -*-> e"),
+    ("MR-One", 1, "e -*-> e'"),
+    ("MR-Multi", 2, "e -*-> e''")
+];
+
+// 体系ごとに使える規則
+// EvalNatExp と ReduceNatExp は Nat の規則も使う
+fn in_nat_system(sys: System, rule: &str) -> bool {
+    let peano = rule.starts_with("P-") || rule.starts_with("T-");
+    match sys {
+        System::Nat => peano,
+        System::CompareNat1 => rule == "L-Succ" || rule == "L-Trans",
+        System::CompareNat2 => rule == "L-Zero" || rule == "L-SuccSucc",
+        System::CompareNat3 => rule == "L-Succ" || rule == "L-SuccR",
+        System::EvalNatExp => peano || rule.starts_with("E-"),
+        System::ReduceNatExp => peano || rule.starts_with("R-") || rule.starts_with("DR-") || rule.starts_with("MR-"),
+        _ => false
+    }
+}
+
+// 同じ判断に複数の導出がありうる (L-Trans の中間の数や MR-Multi の分け方など) ので,
+// 答えと比べるのではなく規則ごとに結論と前提の関係を調べる
+fn check_nat(d: &Derivation, t: &Tokenized<NatJudgment>, sys: System) -> Result<(), String> {
+    use NatJudgment as J;
+
+    let rule = d.rule.as_str();
+    let (_, count, form) = match NAT_RULES.iter().find(|(name, _, _)| *name == rule) {
+        Some(r) if in_nat_system(sys, rule) => r,
+        _ => {
+            let rules = NAT_RULES.iter().map(|r| r.0).filter(|r| in_nat_system(sys, r)).collect::<Vec<_>>();
+            return Err(format!("unknown rule {} in {:?}; expected one of {}", rule, sys, rules.join(", ")))
+        }
+    };
+    premise_count(d, *count)?;
+    let j = t.judgment()?;
+    let ps = t.premises.iter().enumerate()
+        .map(|(i, p)| p.judgment().map_err(|e| format!("premise {}: {}", i + 1, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mismatch = || Err(format!("{} does not apply to `{}`; it derives `{}`", rule, j, form));
+    let premise = |i: usize, expected: J| -> Result<(), String> {
+        if *ps[i] != expected {
+            return Err(format!("premise {}: expected `{}`, found `{}`", i + 1, expected, ps[i]))
+        }
+        Ok(())
+    };
+    let shape = |i: usize, expected: String| format!("premise {}: expected `{}`, found `{}`", i + 1, expected, ps[i]);
+    let n = |n: usize| NatExp::Nat(n);
+
+    match (rule, j) {
+        ("P-Zero", J::Plus(0, n2, n3)) if n2 == n3 => Ok(()),
+        ("P-Succ", J::Plus(n1, n2, n3)) if *n1 > 0 && *n3 > 0 => premise(0, J::Plus(n1 - 1, *n2, n3 - 1)),
+        ("T-Zero", J::Times(0, _, 0)) => Ok(()),
+        ("T-Succ", J::Times(n1, n2, n4)) if *n1 > 0 => match ps[0] {
+            J::Times(m1, m2, n3) if *m1 == n1 - 1 && m2 == n2 => premise(1, J::Plus(*n2, *n3, *n4)),
+            _ => Err(shape(0, format!("{} times {} is n3", n(n1 - 1), n(*n2))))
+        },
+        ("L-Succ", J::Less(n1, n2)) if *n2 == n1 + 1 => Ok(()),
+        ("L-Trans", J::Less(n1, n3)) => match ps[0] {
+            J::Less(m1, n2) if m1 == n1 => premise(1, J::Less(*n2, *n3)),
+            _ => Err(shape(0, format!("{} is less than n2", n(*n1))))
+        },
+        ("L-Zero", J::Less(0, n2)) if *n2 > 0 => Ok(()),
+        ("L-SuccSucc", J::Less(n1, n2)) if *n1 > 0 && *n2 > 0 => premise(0, J::Less(n1 - 1, n2 - 1)),
+        ("L-SuccR", J::Less(n1, n2)) if *n2 > 0 => premise(0, J::Less(*n1, n2 - 1)),
+        ("E-Const", J::EvalTo(NatExp::Nat(n1), n2)) if n1 == n2 => Ok(()),
+        ("E-Plus", J::EvalTo(NatExp::Plus(e1, e2), n3)) | ("E-Times", J::EvalTo(NatExp::Times(e1, e2), n3)) => {
+            let result = |i: usize, e: &NatExp| match ps[i] {
+                J::EvalTo(e1, n1) if e1 == e => Ok(*n1),
+                _ => Err(shape(i, format!("{} evalto n{}", e, i + 1)))
+            };
+            let (n1, n2) = (result(0, e1)?, result(1, e2)?);
+            premise(2, if rule == "E-Plus" { J::Plus(n1, n2, *n3) } else { J::Times(n1, n2, *n3) })
+        }
+        ("MR-Zero", J::MultiReduce(e1, e2)) if e1 == e2 => Ok(()),
+        ("MR-One", J::MultiReduce(e1, e2)) => premise(0, J::Reduce(e1.clone(), e2.clone())),
+        ("MR-Multi", J::MultiReduce(e1, e3)) => match ps[0] {
+            J::MultiReduce(e, e2) if e == e1 => premise(1, J::MultiReduce(e2.clone(), e3.clone())),
+            _ => Err(shape(0, format!("{} -*-> e'", e1)))
+        },
+        (_, J::Reduce(e1, e2)) if rule.starts_with("R-") => check_reduce(rule, e1, e2, J::Reduce, &premise).unwrap_or_else(mismatch),
+        (_, J::DetReduce(e1, e2)) if rule.starts_with("DR-") => check_reduce(rule, e1, e2, J::DetReduce, &premise).unwrap_or_else(mismatch),
+        _ => mismatch()
+    }
+}
+
+// R-, DR- の規則
+// 規則の形に合わなければ None
+// DR-PlusR, DR-TimesR は左が数になってから右を簡約する
+fn check_reduce(
+    rule: &str,
+    e1: &NatExp,
+    e2: &NatExp,
+    step: fn(NatExp, NatExp) -> NatJudgment,
+    premise: &dyn Fn(usize, NatJudgment) -> Result<(), String>
+) -> Option<Result<(), String>> {
+    let det = rule.starts_with("DR-");
+    let op = &rule[rule.find('-').unwrap() + 1..];
+    match (op, e1, e2) {
+        ("Plus", NatExp::Plus(l, r), NatExp::Nat(n3)) => match (l.as_ref(), r.as_ref()) {
+            (NatExp::Nat(n1), NatExp::Nat(n2)) => Some(premise(0, NatJudgment::Plus(*n1, *n2, *n3))),
+            _ => None
+        },
+        ("Times", NatExp::Times(l, r), NatExp::Nat(n3)) => match (l.as_ref(), r.as_ref()) {
+            (NatExp::Nat(n1), NatExp::Nat(n2)) => Some(premise(0, NatJudgment::Times(*n1, *n2, *n3))),
+            _ => None
+        },
+        ("PlusL", NatExp::Plus(l1, r1), NatExp::Plus(l2, r2))
+            | ("TimesL", NatExp::Times(l1, r1), NatExp::Times(l2, r2)) if r1 == r2 =>
+            Some(premise(0, step(*l1.clone(), *l2.clone()))),
+        ("PlusR", NatExp::Plus(l1, r1), NatExp::Plus(l2, r2))
+            | ("TimesR", NatExp::Times(l1, r1), NatExp::Times(l2, r2)) if l1 == l2 && (!det || matches!(l1.as_ref(), NatExp::Nat(_))) =>
+            Some(premise(0, step(*r1.clone(), *r2.clone()))),
+        _ => None
+    }
+}
+
+// 規則名と前提の数
+// 型付けの規則は式の形だけで決まる
+const TYPING_RULES: &[(&str, usize)] = &[
+    ("T-Int", 0), ("T-Bool", 0), ("T-Nil", 0),
+    ("T-Plus", 2), ("T-Minus", 2), ("T-Times", 2), ("T-Lt", 2),
+    ("T-If", 3), ("T-Var", 0), ("T-Let", 2), ("T-Fun", 1), ("T-App", 2),
+    ("T-LetRec", 2), ("T-Cons", 2), ("T-Match", 3)
+];
+
+fn typing_rule(expr: &Expr) -> Option<&'static str> {
+    match expr {
+        Expr::Value(Value::Int(_)) => Some("T-Int"),
+        Expr::Value(Value::Bool(_)) => Some("T-Bool"),
+        Expr::Value(Value::Nil) => Some("T-Nil"),
+        Expr::Prim(Prim::Add(_, _)) => Some("T-Plus"),
+        Expr::Prim(Prim::Sub(_, _)) => Some("T-Minus"),
+        Expr::Prim(Prim::Mul(_, _)) => Some("T-Times"),
+        Expr::Prim(Prim::LessThan(_, _)) => Some("T-Lt"),
+        Expr::IfThenElse(_, _, _) => Some("T-If"),
+        Expr::Ident(_) => Some("T-Var"),
+        Expr::Let(_, _, _) => Some("T-Let"),
+        Expr::Fun(_, _) => Some("T-Fun"),
+        Expr::App(_, _) => Some("T-App"),
+        Expr::LetRec(_, _, _, _) => Some("T-LetRec"),
+        Expr::Cons(_, _) => Some("T-Cons"),
+        Expr::Match(_, clauses) if match_clauses(clauses).is_some() => Some("T-Match"),
+        _ => None
+    }
+}
+
+// 型変数は導出全体で同じ名前なら同じ型変数として比べる
+// T-Var では結論の型が型スキームの具体化になっているか,
+// PolyTypingML4 の T-Let, T-LetRec では環境に加えた型スキームが一般化した結果になっているかを調べる
+fn check_typing(d: &Derivation, t: &Tokenized<TypingTokens>, sys: System) -> Result<(), String> {
+    let (env, expr, ty) = t.parse()?;
+    let ps = &t.premises;
+
+    let rule = typing_rule(&expr).ok_or_else(|| format!("{:?} does not support `{}`", sys, expr))?;
+    if d.rule != rule {
+        if !TYPING_RULES.iter().any(|(name, _)| *name == d.rule) {
+            return Err(format!("unknown rule {}; expected {}", d.rule, rule))
+        }
+        return Err(format!("{} does not apply to `{}`; expected {}", d.rule, expr, rule))
+    }
+    premise_count(d, TYPING_RULES.iter().find(|(name, _)| *name == rule).unwrap().1)?;
+
+    // T-App: argument type bool does not match parameter type int
+    let same = |what: &str, actual: &Type, of: &str, expected: &Type| -> Result<(), String> {
+        if actual != expected {
+            return Err(format!("{}: {} type {} does not match {} type {}", rule, what, actual, of, expected))
+        }
+        Ok(())
+    };
+
+    match &expr {
+        Expr::Value(Value::Int(_)) => same("conclusion", &ty, "integer", &Type::Int),
+        Expr::Value(Value::Bool(_)) => same("conclusion", &ty, "boolean", &Type::Bool),
+        Expr::Value(Value::Nil) => match ty {
+            Type::List(_) => Ok(()),
+            _ => Err(format!("T-Nil: conclusion type {} is not a list type", ty))
+        },
+        Expr::Prim(p) => {
+            let (l, r) = operands(p);
+            same("left operand", &premise_type(ps, 0, &env, l)?, "expected", &Type::Int)?;
+            same("right operand", &premise_type(ps, 1, &env, r)?, "expected", &Type::Int)?;
+            let result = match p {
+                Prim::LessThan(_, _) => Type::Bool,
+                _ => Type::Int
+            };
+            same("conclusion", &ty, "result", &result)
+        }
+        Expr::IfThenElse(cond, then, els) => {
+            same("condition", &premise_type(ps, 0, &env, cond)?, "expected", &Type::Bool)?;
+            same("then-branch", &premise_type(ps, 1, &env, then)?, "conclusion", &ty)?;
+            same("else-branch", &premise_type(ps, 2, &env, els)?, "conclusion", &ty)
+        }
+        Expr::Ident(x) => {
+            let s = env.get(x).ok_or_else(|| format!("{} is not bound", x))?;
+            if !instance(s, &ty) {
+                return Err(format!("T-Var: {} is not an instance of {}, the type of {}", ty, s, x))
+            }
+            Ok(())
+        }
+        Expr::Let(x, e1, e2) => {
+            let t1 = premise_type(ps, 0, &env, e1)?;
+            let t2 = premise_type(ps, 1, &env.pushed_scheme(x, generalize(sys, &env, &t1)), e2)?;
+            same("body", &t2, "conclusion", &ty)
+        }
+        Expr::Fun(x, body) => match &ty {
+            Type::Fun(t1, t2) => same("body", &premise_type(ps, 0, &env.pushed(x, *t1.clone()), body)?, "return", t2),
+            _ => Err(format!("T-Fun: conclusion type {} is not a function type", ty))
+        },
+        Expr::App(e1, e2) => {
+            let (t1, t2) = match premise_type(ps, 0, &env, e1)? {
+                Type::Fun(t1, t2) => (*t1, *t2),
+                t => return Err(format!("T-App: {} is applied but has type {}, not a function type", e1, t))
+            };
+            same("argument", &premise_type(ps, 1, &env, e2)?, "parameter", &t1)?;
+            same("result", &t2, "conclusion", &ty)
+        }
+        Expr::LetRec(f, x, e1, e2) => {
+            // f と x の型は前提 1 の環境に書かれている
+            let (premise_env, _, _) = ps[0].parse().map_err(|e| format!("premise 1: {}", e))?;
+            let (t1, t2) = match premise_env.0.as_slice() {
+                [(_, Scheme(_, t1)), (_, Scheme(_, Type::Fun(p, t2))), ..] if **p == *t1 => (t1.clone(), *t2.clone()),
+                _ => return Err(format!("premise 1: expected `{}, {} : τ1 -> τ2, {} : τ1 |- ...`, found `{} ...`", env, f, x, premise_env.form()))
+            };
+            let fun_t = Type::Fun(Box::new(t1.clone()), Box::new(t2.clone()));
+            let body_t = premise_type(ps, 0, &env.pushed(f, fun_t.clone()).pushed(x, t1), e1)?;
+            same("body", &body_t, "return", &t2)?;
+            let t3 = premise_type(ps, 1, &env.pushed_scheme(f, generalize(sys, &env, &fun_t)), e2)?;
+            same("body", &t3, "conclusion", &ty)
+        }
+        Expr::Cons(e1, e2) => match &ty {
+            Type::List(elem) => {
+                same("head", &premise_type(ps, 0, &env, e1)?, "element", elem)?;
+                same("tail", &premise_type(ps, 1, &env, e2)?, "conclusion", &ty)
+            }
+            _ => Err(format!("T-Cons: conclusion type {} is not a list type", ty))
+        },
+        Expr::Match(e0, clauses) => {
+            let (e1, x, y, e2) = match_clauses(clauses).unwrap();
+            let elem = match premise_type(ps, 0, &env, e0)? {
+                Type::List(elem) => *elem,
+                t => return Err(format!("T-Match: matched expression {} has type {}, not a list type", e0, t))
+            };
+            same("[] branch", &premise_type(ps, 1, &env, e1)?, "conclusion", &ty)?;
+            let env2 = env.pushed(x, elem.clone()).pushed(y, Type::List(Box::new(elem)));
+            same("x :: y branch", &premise_type(ps, 2, &env2, e2)?, "conclusion", &ty)
+        }
+        _ => unreachable!("internal: expression is checked by typing_rule")
+    }
+}
+
+// i 番目の前提が Γ |- e : τ の形なら τ を返す
+fn premise_type<'a>(ps: &'a [Tokenized<TypingTokens>], i: usize, env: &TypeEnv<'a>, expr: &Expr<'a>) -> Result<Type, String> {
+    let (actual_env, actual_expr, t) = ps[i].parse().map_err(|e| format!("premise {}: {}", i + 1, e))?;
+    if actual_expr.to_string() != expr.to_string() {
+        return Err(format!("premise {}: expected `{}`, found `{}`", i + 1, expr, actual_expr))
+    }
+    let same = env.0.len() == actual_env.0.len()
+        && env.0.iter().zip(&actual_env.0).all(|((x, s1), (y, s2))| x == y && same_scheme(s1, s2));
+    if !same {
+        // 違うのが最後に加えた変数だけなら, その型スキームを示す
+        if let ([(x, s1), rest1 @ ..], [(y, s2), rest2 @ ..]) = (env.0.as_slice(), actual_env.0.as_slice()) {
+            if x == y && rest1.len() == rest2.len()
+                && rest1.iter().zip(rest2).all(|((x, s1), (y, s2))| x == y && same_scheme(s1, s2)) {
+                return Err(format!("premise {}: expected {} : {} in the environment, found {} : {}", i + 1, x, s1, y, s2))
+            }
+        }
+        return Err(format!("premise {}: expected environment `{}`, found `{}`", i + 1, env, actual_env))
+    }
+    Ok(t)
+}
+
+// 量化した変数の名前だけが違う型スキームは同じとみなす
+fn same_scheme(s1: &Scheme, s2: &Scheme) -> bool {
+    if s1.0.len() != s2.0.len() {
+        return false
+    }
+    // 量化した変数を出てくる順に, どちらにも出てこない番号へ付け替えて比べる
+    let mut vars = vec![];
+    type_vars(&s1.1, &mut vars);
+    type_vars(&s2.1, &mut vars);
+    let base = vars.iter().chain(&s1.0).chain(&s2.0).max().map_or(0, |n| n + 1);
+    let canonical = |Scheme(bound, t): &Scheme| {
+        let mut order = vec![];
+        type_vars(t, &mut order);
+        order.retain(|n| bound.contains(n));
+        let fresh = order.iter().enumerate().map(|(i, n)| (*n, Type::Var(base + i))).collect::<HashMap<_, _>>();
+        substitute(t, &fresh)
+    };
+    canonical(s1) == canonical(s2)
+}
+
+// τ が σ の量化した変数に型を入れたものか
+fn instance(s: &Scheme, t: &Type) -> bool {
+    fn matches(bound: &[usize], s: &Type, t: &Type, m: &mut HashMap<usize, Type>) -> bool {
+        match (s, t) {
+            (Type::Var(a), _) if bound.contains(a) => match m.get(a) {
+                Some(u) => u == t,
+                None => {
+                    m.insert(*a, t.clone());
+                    true
+                }
+            },
+            (Type::Fun(l1, r1), Type::Fun(l2, r2)) => matches(bound, l1, l2, m) && matches(bound, r1, r2, m),
+            (Type::List(s), Type::List(t)) => matches(bound, s, t, m),
+            _ => s == t
+        }
+    }
+    matches(&s.0, &s.1, t, &mut HashMap::new())
+}
+
+// Γ に自由に出てこない型変数を量化する
+// TypingML4 では一般化しない
+fn generalize(sys: System, env: &TypeEnv, t: &Type) -> Scheme {
+    if sys != System::PolyTypingML4 {
+        return Scheme(vec![], t.clone())
+    }
+    let mut free = vec![];
+    for (_, Scheme(bound, s)) in &env.0 {
+        let mut vars = vec![];
+        type_vars(s, &mut vars);
+        free.extend(vars.into_iter().filter(|n| !bound.contains(n)));
+    }
+    let mut vars = vec![];
+    type_vars(t, &mut vars);
+    vars.retain(|n| !free.contains(n));
+
+    Scheme(vars, t.clone())
+}
+
+fn premise_count(d: &Derivation, n: usize) -> Result<(), String> {
+    if d.premises.len() != n {
+        return Err(format!("{} takes {} premise(s), found {}", d.rule, n, d.premises.len()))
+    }
+    Ok(())
+}
+
+// i 番目の前提が ε |- e evalto v の形なら v を返す
+fn premise_value<'a>(ps: &'a [Tokenized<EvalTokens>], i: usize, env: &Env<'a>, expr: &Expr<'a>) -> Result<Value<'a>, String> {
+    premise_outcome(ps, i, env, expr)?.ok_or_else(|| format!("premise {} evaluates to error", i + 1))
+}
+
+// evalto error なら None
+fn premise_outcome<'a>(ps: &'a [Tokenized<EvalTokens>], i: usize, env: &Env<'a>, expr: &Expr<'a>) -> Result<Option<Value<'a>>, String> {
+    let (Form(actual_env, actual_expr), v) = ps[i].parse().map_err(|e| format!("premise {}: {}", i + 1, e))?;
+    let expected = judge(env, expr);
+    let actual = judge(&actual_env, &actual_expr);
+    if expected != actual {
+        return Err(format!("premise {}: expected `{} evalto ...`, found `{} evalto ...`", i + 1, expected, actual))
+    }
+    Ok(v)
+}
+
+// i 番目の前提が ε |- e evalto error の形か
+fn expect_error<'a>(ps: &'a [Tokenized<EvalTokens>], i: usize, env: &Env<'a>, expr: &Expr<'a>, rule: &str) -> Result<(), String> {
+    match premise_outcome(ps, i, env, expr)? {
+        None => Ok(()),
+        Some(v) => Err(format!("{} needs premise {} to evaluate to error, found {}", rule, i + 1, v))
+    }
+}
+
+fn same_value(expected: &Value, actual: &Value) -> Result<(), String> {
+    if expected.to_string() != actual.to_string() {
+        return Err(format!("expected value {}, found {}", expected, actual))
+    }
+    Ok(())
+}
+
+fn check_arith(d: &Derivation) -> Result<(), String> {
+    let a = arith(&d.judgment)?;
+    let (rule, result) = match a.op.as_str() {
+        "plus" => ("B-Plus", (a.left + a.right).to_string()),
+        "minus" => ("B-Minus", (a.left - a.right).to_string()),
+        "times" => ("B-Times", (a.left * a.right).to_string()),
+        "less than" => ("B-Lt", (a.left < a.right).to_string()),
+        op => return Err(format!("unknown operator {}", op))
+    };
+    if d.rule != rule {
+        return Err(format!("{} does not apply to `{}`; expected {}", d.rule, d.judgment, rule))
+    }
+    premise_count(d, 0)?;
+    if a.result != result {
+        return Err(format!("{} {} {} is {}, not {}", a.left, a.op, a.right, result, a.result))
+    }
+    Ok(())
+}
+
+fn operands<'a, 'b>(p: &'b Prim<'a>) -> (&'b Expr<'a>, &'b Expr<'a>) {
+    match p {
+        Prim::Add(l, r) | Prim::Sub(l, r) | Prim::Mul(l, r) | Prim::LessThan(l, r) => (l, r)
+    }
+}
+
+fn bind<'a>(env: &Env<'a>, x: &'a String, v: Value<'a>) -> Env<'a> {
+    Env(vec![EnvVar(x, Box::new(Expr::Value(v)))]).appended(env)
+}
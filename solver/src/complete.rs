@@ -0,0 +1,70 @@
+use crate::derivation::Derivation;
+use crate::diff::{derive_reference, derive_claimed, key};
+use crate::solver::System;
+
+// 書きかけの導出の穴 (?) を埋める
+// 結論の環境と式から solver で導出を組み立て, 書かれた部分がそれと合っていれば穴をそこから写す
+// どの体系でも導出は結論の環境と式で一つに決まるので, 合わなければ埋めようがない
+pub fn complete(written: &Derivation, sys: System) -> anyhow::Result<Derivation> {
+    let reference = match written.judgment.contains('?') {
+        true => derive_reference(&written.judgment, sys)?,
+        false => derive_claimed(&written.judgment, sys)?
+    };
+    fill(written, &reference)
+}
+
+fn fill(written: &Derivation, reference: &Derivation) -> anyhow::Result<Derivation> {
+    let at = |message: String| anyhow::anyhow!("line {}: {}: {}", written.line, written.judgment, message);
+
+    if !matches_holes(&key(&written.judgment), &key(&reference.judgment)) {
+        return Err(at(format!("the solver derives `{}` here", reference.judgment.trim())))
+    }
+    if written.rule != "?" && written.rule != reference.rule {
+        return Err(at(format!("{} does not apply; this judgment is derived by {}", written.rule, reference.rule)))
+    }
+    if !written.hole && written.premises.len() != reference.premises.len() {
+        return Err(at(format!("{} takes {} premise(s), found {}", reference.rule, reference.premises.len(), written.premises.len())))
+    }
+
+    let judgment = match written.judgment.contains('?') {
+        true => written_like(&written.judgment, &reference.judgment),
+        false => written.judgment.clone()
+    };
+    let premises = match written.hole {
+        true => reference.premises.iter().map(trim).collect(),
+        false => written.premises.iter().zip(&reference.premises)
+            .map(|(w, r)| fill(w, r))
+            .collect::<anyhow::Result<Vec<_>>>()?
+    };
+    Ok(Derivation { judgment, rule: reference.rule.clone(), premises, line: written.line, hole: false })
+}
+
+// 空の環境の |- は書かれた方に合わせる
+fn written_like(written: &str, judgment: &str) -> String {
+    match written.starts_with("|-") {
+        true => judgment.trim().to_string(),
+        false => key(judgment)
+    }
+}
+
+// solver の導出の判断は空の環境で空白から始まる
+fn trim(d: &Derivation) -> Derivation {
+    Derivation::new(d.judgment.trim().to_string(), &d.rule, d.premises.iter().map(trim).collect())
+}
+
+// ? は空でない任意の文字列に合う
+fn matches_holes(pattern: &str, s: &str) -> bool {
+    let (p, s) = (pattern.chars().collect::<Vec<_>>(), s.chars().collect::<Vec<_>>());
+    // ok[i][j]: p[i..] が s[j..] に合う
+    let mut ok = vec![vec![false; s.len() + 1]; p.len() + 1];
+    ok[p.len()][s.len()] = true;
+    for i in (0..p.len()).rev() {
+        for j in (0..s.len()).rev() {
+            ok[i][j] = match p[i] {
+                '?' => ok[i + 1][j + 1] || ok[i][j + 1],
+                c => c == s[j] && ok[i + 1][j + 1]
+            };
+        }
+    }
+    ok[0][0]
+}
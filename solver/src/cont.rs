@@ -0,0 +1,300 @@
+use crate::expr::{Expr, Prim, Value, Cont, ContForm, Frame, Env, EnvVar, Pattern};
+use crate::derivation::Derivation;
+
+// BNF
+// EvalContML1
+// e ∈ Exp ::= i | b | e op e | if e then e else e
+// k ∈ Cont ::= _ | {_ op e} >> k | {v op _} >> k | {if _ then e else e} >> k
+//
+// e >> k evalto v
+// v => k evalto v
+//
+// EvalContML4
+// e ∈ Exp ::= ... | x | let x = e in e | fun x -> e | e e | let rec f = fun x -> e in e
+//             | [] | e :: e | match e with [] -> e | x :: y -> e | letcc x in e
+// v ∈ Value ::= ... | (ε)[fun x -> e] | (ε)[rec f = fun x -> e] | [] | v :: v | [k]
+// k ∈ Cont ::= _ | {ε |- _ op e} >> k | {v op _} >> k | {ε |- if _ then e else e} >> k
+//            | {ε |- let x = _ in e} >> k | {ε |- _ e} >> k | {v _} >> k
+//            | {ε |- _ :: e} >> k | {v :: _} >> k
+//            | {ε |- match _ with [] -> e | x :: y -> e} >> k
+//
+// ε |- e >> k evalto v
+// v => k evalto v
+//
+// 継続が評価の残り全部を表すので, 導出中の判断はすべて同じ値に評価される
+// どの規則も前提の判断は (B- の前提を除いて) 一つだけなので, 導出は判断の列になる
+
+// 一段分の規則の適用
+struct Step<'a> {
+    rule: &'static str,
+    // C-Plus などの B- の前提
+    premise: Option<Derivation>,
+    // C-Ret のときは None
+    next: Option<ContForm<'a>>
+}
+
+pub fn solve(form: &ContForm) -> anyhow::Result<()> {
+    print!("{}", derive(form)?);
+    Ok(())
+}
+
+pub fn derive(form: &ContForm) -> anyhow::Result<Derivation> {
+    // 先に最後まで評価して結果の値を求める
+    let mut steps = vec![];
+    let mut forms = vec![form.clone()];
+    loop {
+        let current = forms.last().unwrap();
+        let s = step(current)?;
+        match &s.next {
+            Some(next) => {
+                let next = next.clone();
+                steps.push(s);
+                forms.push(next);
+            }
+            None => {
+                steps.push(s);
+                break
+            }
+        }
+    }
+    let result = match forms.last().unwrap() {
+        ContForm::Ret(v, _) => v.clone(),
+        _ => unreachable!("internal: C-Ret must be the last step")
+    };
+
+    // 最後の C-Ret から順に, 一つ後の段を前提にして組み立てる
+    let mut derivation = None;
+    for (form, s) in forms.iter().zip(steps).rev() {
+        let premises = s.premise.into_iter().chain(derivation).collect();
+        derivation = Some(Derivation::new(format!("{} evalto {}", cont_form(form), result), s.rule, premises));
+    }
+
+    Ok(derivation.unwrap())
+}
+
+fn step<'a>(form: &ContForm<'a>) -> anyhow::Result<Step<'a>> {
+    match form {
+        ContForm::Eval(env, expr, k) => eval(env, expr, k),
+        ContForm::Ret(v, k) => ret(v, k)
+    }
+}
+
+fn eval<'a>(env: &Option<Env<'a>>, expr: &Expr<'a>, k: &Cont<'a>) -> anyhow::Result<Step<'a>> {
+    // 式を評価するフレームは判断と同じ環境を持つ
+    let frame = |e: Expr<'a>| Frame(env.clone(), e);
+    let (rule, next) = match expr {
+        Expr::Value(Value::Bool(_)) => ("E-Bool", ContForm::Ret(literal(expr)?, k.clone())),
+        Expr::Value(Value::Int(_)) => ("E-Int", ContForm::Ret(literal(expr)?, k.clone())),
+        Expr::Prim(p) => {
+            let (l, r) = operands(p);
+            let k = k.pushed(frame(Expr::Prim(with_operands(p, Expr::Hole, r.clone()))));
+            ("E-BinOp", ContForm::Eval(env.clone(), l.clone(), k))
+        }
+        Expr::IfThenElse(cond, then, els) => {
+            let k = k.pushed(frame(Expr::IfThenElse(Box::new(Expr::Hole), then.clone(), els.clone())));
+            ("E-If", ContForm::Eval(env.clone(), *cond.clone(), k))
+        }
+        Expr::Ident(x) => {
+            let v = get_env_var(require_env(env, expr)?, x).ok_or_else(|| anyhow::anyhow!("unbound variable: {}", x))?;
+            ("E-Var", ContForm::Ret(v, k.clone()))
+        }
+        Expr::Let(x, e1, e2) => {
+            require_env(env, expr)?;
+            let k = k.pushed(frame(Expr::Let(x, Box::new(Expr::Hole), e2.clone())));
+            ("E-Let", ContForm::Eval(env.clone(), *e1.clone(), k))
+        }
+        Expr::Fun(x, body) => {
+            let closure = Value::Closure(require_env(env, expr)?.clone(), x, body.clone());
+            ("E-Fun", ContForm::Ret(closure, k.clone()))
+        }
+        Expr::App(e1, e2) => {
+            require_env(env, expr)?;
+            let k = k.pushed(frame(Expr::App(Box::new(Expr::Hole), e2.clone())));
+            ("E-App", ContForm::Eval(env.clone(), *e1.clone(), k))
+        }
+        Expr::LetRec(f, x, e1, e2) => {
+            let env = require_env(env, expr)?;
+            let closure = Value::RecClosure(env.clone(), f, x, e1.clone());
+            let env = Env(vec![EnvVar(f, Box::new(Expr::Value(closure)))]).appended(env);
+            ("E-LetRec", ContForm::Eval(Some(env), *e2.clone(), k.clone()))
+        }
+        Expr::Value(Value::Nil) => {
+            require_env(env, expr)?;
+            ("E-Nil", ContForm::Ret(Value::Nil, k.clone()))
+        }
+        Expr::Cons(e1, e2) => {
+            require_env(env, expr)?;
+            let k = k.pushed(frame(Expr::Cons(Box::new(Expr::Hole), e2.clone())));
+            ("E-Cons", ContForm::Eval(env.clone(), *e1.clone(), k))
+        }
+        Expr::Match(e0, clauses) => {
+            require_env(env, expr)?;
+            let k = k.pushed(frame(Expr::Match(Box::new(Expr::Hole), clauses.clone())));
+            ("E-Match", ContForm::Eval(env.clone(), *e0.clone(), k))
+        }
+        Expr::LetCc(x, e) => {
+            // 今の継続 k を値 [k] として x に束縛する
+            let env = Env(vec![EnvVar(x, Box::new(Expr::Value(Value::Cont(k.clone()))))]).appended(require_env(env, expr)?);
+            ("E-LetCc", ContForm::Eval(Some(env), *e.clone(), k.clone()))
+        }
+        _ => return Err(anyhow::anyhow!("EvalContML does not support: {}", expr))
+    };
+
+    Ok(Step { rule, premise: None, next: Some(next) })
+}
+
+fn ret<'a>(v: &Value<'a>, k: &Cont<'a>) -> anyhow::Result<Step<'a>> {
+    let Frame(env, frame) = match k.0.first() {
+        None => return Ok(Step { rule: "C-Ret", premise: None, next: None }),
+        Some(frame) => frame
+    };
+    let rest = k.rest();
+    let (rule, premise, next) = match frame {
+        Expr::Prim(p) => {
+            match operands(p) {
+                (Expr::Hole, e) => {
+                    // 値を持つフレームは環境を持たない
+                    let k = rest.pushed(Frame(None, Expr::Prim(with_operands(p, Expr::Value(v.clone()), Expr::Hole))));
+                    ("C-EvalR", None, ContForm::Eval(env.clone(), e.clone(), k))
+                }
+                (l, Expr::Hole) => {
+                    let (rule, op) = match p {
+                        Prim::Add(_, _) => ("C-Plus", "plus"),
+                        Prim::Sub(_, _) => ("C-Minus", "minus"),
+                        Prim::Mul(_, _) => ("C-Times", "times"),
+                        Prim::LessThan(_, _) => ("C-Lt", "less than")
+                    };
+                    let l = literal(l)?;
+                    let r = binop(p, &l, v)?;
+                    let premise = Derivation::new(format!("{} {} {} is {}", l, op, v, r), &format!("B-{}", &rule[2..]), vec![]);
+                    (rule, Some(premise), ContForm::Ret(r, rest))
+                }
+                _ => return Err(anyhow::anyhow!("invalid frame: {}", p))
+            }
+        }
+        Expr::IfThenElse(cond, then, els) if matches!(cond.as_ref(), Expr::Hole) => {
+            match v {
+                Value::Bool(true) => ("C-IfT", None, ContForm::Eval(env.clone(), *then.clone(), rest)),
+                Value::Bool(false) => ("C-IfF", None, ContForm::Eval(env.clone(), *els.clone(), rest)),
+                _ => return Err(anyhow::anyhow!("expect bool: {}", v))
+            }
+        }
+        Expr::Let(x, e1, e2) if matches!(e1.as_ref(), Expr::Hole) => {
+            let env = Env(vec![EnvVar(x, Box::new(Expr::Value(v.clone())))]).appended(require_env(env, frame)?);
+            ("C-LetBody", None, ContForm::Eval(Some(env), *e2.clone(), rest))
+        }
+        Expr::App(e1, e2) if matches!(e1.as_ref(), Expr::Hole) => {
+            let k = rest.pushed(Frame(None, Expr::App(Box::new(Expr::Value(v.clone())), Box::new(Expr::Hole))));
+            ("C-EvalArg", None, ContForm::Eval(env.clone(), *e2.clone(), k))
+        }
+        Expr::App(f, e2) if matches!(e2.as_ref(), Expr::Hole) => {
+            match f.as_ref() {
+                Expr::Value(Value::Closure(cenv, x, body)) => {
+                    let env = Env(vec![EnvVar(x, Box::new(Expr::Value(v.clone())))]).appended(cenv);
+                    ("C-EvalFun", None, ContForm::Eval(Some(env), *body.clone(), rest))
+                }
+                Expr::Value(closure @ Value::RecClosure(cenv, fun, x, body)) => {
+                    let env = Env(vec![
+                        EnvVar(x, Box::new(Expr::Value(v.clone()))),
+                        EnvVar(fun, Box::new(Expr::Value(closure.clone())))
+                    ]).appended(cenv);
+                    ("C-EvalFunR", None, ContForm::Eval(Some(env), *body.clone(), rest))
+                }
+                // 継続を適用すると今の継続は捨てられる
+                Expr::Value(Value::Cont(k1)) => ("C-EvalFunC", None, ContForm::Ret(v.clone(), k1.clone())),
+                _ => return Err(anyhow::anyhow!("expect closure or continuation: {}", f))
+            }
+        }
+        Expr::Cons(e1, e2) if matches!(e1.as_ref(), Expr::Hole) => {
+            let k = rest.pushed(Frame(None, Expr::Cons(Box::new(Expr::Value(v.clone())), Box::new(Expr::Hole))));
+            ("C-EvalConsR", None, ContForm::Eval(env.clone(), *e2.clone(), k))
+        }
+        Expr::Cons(e1, e2) if matches!(e2.as_ref(), Expr::Hole) => {
+            let l = e1.to_value().ok_or_else(|| anyhow::anyhow!("expect value: {}", e1))?;
+            ("C-Cons", None, ContForm::Ret(Value::Cons(Box::new(l), Box::new(v.clone())), rest))
+        }
+        Expr::Match(e0, clauses) if matches!(e0.as_ref(), Expr::Hole) => {
+            match (clauses.as_slice(), v) {
+                ([(Pattern::Nil, e2), _], Value::Nil) =>
+                    ("C-MatchNil", None, ContForm::Eval(env.clone(), e2.clone(), rest)),
+                ([_, (Pattern::Cons(x, y), e3)], Value::Cons(v1, v2)) => {
+                    let (x, y) = match (x.as_ref(), y.as_ref()) {
+                        (Pattern::Var(x), Pattern::Var(y)) => (*x, *y),
+                        _ => return Err(anyhow::anyhow!("invalid frame: {}", frame))
+                    };
+                    let env = Env(vec![
+                        EnvVar(y, Box::new(Expr::Value(*v2.clone()))),
+                        EnvVar(x, Box::new(Expr::Value(*v1.clone())))
+                    ]).appended(require_env(env, frame)?);
+                    ("C-MatchCons", None, ContForm::Eval(Some(env), e3.clone(), rest))
+                }
+                _ => return Err(anyhow::anyhow!("match failure: {}", v))
+            }
+        }
+        _ => return Err(anyhow::anyhow!("invalid frame: {}", frame))
+    };
+
+    Ok(Step { rule, premise, next: Some(next) })
+}
+
+// EvalContML1 の判断には環境がないので, 変数などを含む式は評価できない
+fn require_env<'a, 'b>(env: &'b Option<Env<'a>>, expr: &Expr<'a>) -> anyhow::Result<&'b Env<'a>> {
+    env.as_ref().ok_or_else(|| anyhow::anyhow!("EvalContML1 does not support: {}", expr))
+}
+
+fn get_env_var<'a>(env: &Env<'a>, name: &String) -> Option<Value<'a>> {
+    env.0.iter()
+        .find(|e| e.0 == name)
+        .and_then(|e| e.1.to_value())
+}
+
+// -2 のような整数も含めたリテラル
+fn literal<'a>(expr: &Expr<'a>) -> anyhow::Result<Value<'a>> {
+    match expr.to_value() {
+        Some(v @ (Value::Int(_) | Value::Bool(_))) => Ok(v),
+        _ => Err(anyhow::anyhow!("expect value: {}", expr))
+    }
+}
+
+fn operands<'a, 'b>(p: &'b Prim<'a>) -> (&'b Expr<'a>, &'b Expr<'a>) {
+    match p {
+        Prim::Add(l, r) | Prim::Sub(l, r) | Prim::Mul(l, r) | Prim::LessThan(l, r) => (l, r)
+    }
+}
+
+fn with_operands<'a>(p: &Prim<'a>, l: Expr<'a>, r: Expr<'a>) -> Prim<'a> {
+    match p {
+        Prim::Add(_, _) => Prim::Add(Box::new(l), Box::new(r)),
+        Prim::Sub(_, _) => Prim::Sub(Box::new(l), Box::new(r)),
+        Prim::Mul(_, _) => Prim::Mul(Box::new(l), Box::new(r)),
+        Prim::LessThan(_, _) => Prim::LessThan(Box::new(l), Box::new(r))
+    }
+}
+
+fn binop<'a>(p: &Prim<'a>, l: &Value<'a>, r: &Value<'a>) -> anyhow::Result<Value<'a>> {
+    match (p, l, r) {
+        (Prim::Add(_, _), Value::Int(l), Value::Int(r)) => Ok(Value::Int(l + r)),
+        (Prim::Sub(_, _), Value::Int(l), Value::Int(r)) => Ok(Value::Int(l - r)),
+        (Prim::Mul(_, _), Value::Int(l), Value::Int(r)) => Ok(Value::Int(l * r)),
+        (Prim::LessThan(_, _), Value::Int(l), Value::Int(r)) => Ok(Value::Bool(l < r)),
+        _ => Err(anyhow::anyhow!("expect int: {}, {}", l, r))
+    }
+}
+
+// ε |- e >> k / v => k (k が _ のときは >> _ を省略する)
+fn cont_form(form: &ContForm) -> String {
+    match form {
+        ContForm::Eval(env, expr, k) => {
+            let judge = match env {
+                Some(env) => format!("{} {}", env.form(), expr),
+                None => format!("{}", expr)
+            };
+            if k.0.is_empty() {
+                judge
+            } else {
+                format!("{} >> {}", judge, k)
+            }
+        }
+        ContForm::Ret(v, k) => format!("{} => {}", v, k)
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+
+// CoPL の導出
+// judgment by RULE { premise; premise; };
+//
+// 書きかけの導出では値や規則名を ? にしたり, 前提の { } の代わりに ? を書いたりできる
+// judgment by RULE ?;
+//
+// 判断は体系ごとに形が違うので文字列のまま持ち, 調べるときにそれぞれの体系で読む
+// 空白の並びは一つの空白にまとめる
+#[derive(Debug, Clone)]
+pub struct Derivation {
+    pub judgment: String,
+    pub rule: String,
+    pub premises: Vec<Derivation>,
+    // 判断が始まる行 (1 から数える)
+    pub line: usize,
+    // 前提が ? で省略されている
+    pub hole: bool
+}
+
+impl Derivation {
+    // solver が組み立てる導出には行番号がない
+    pub fn new(judgment: String, rule: &str, premises: Vec<Derivation>) -> Derivation {
+        Derivation { judgment, rule: rule.to_string(), premises, line: 0, hole: false }
+    }
+}
+
+// solver の出力の形
+impl fmt::Display for Derivation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.hole {
+            return writeln!(f, "{} by {} ?;", self.judgment, self.rule)
+        }
+        if self.premises.is_empty() {
+            return writeln!(f, "{} by {} {{}};", self.judgment, self.rule)
+        }
+        writeln!(f, "{} by {} {{", self.judgment, self.rule)?;
+        for premise in &self.premises {
+            write!(f, "{}", premise)?;
+        }
+        writeln!(f, "}};")
+    }
+}
+
+// exercise/ のファイルのように
+// * 問題 * の見出し行と // から行末までのコメントがあってもよい
+// 前提の後ろや導出の最後の ; は省略できる
+pub fn parse_derivation(text: &str) -> anyhow::Result<Derivation> {
+    let mut scanner = Scanner { chars: strip(text).chars().collect(), pos: 0 };
+    let derivation = scanner.derivation()?;
+    scanner.skip_space();
+    if scanner.peek() == Some(';') {
+        scanner.pos += 1;
+        scanner.skip_space();
+    }
+    match scanner.peek() {
+        None => Ok(derivation),
+        Some(c) => Err(scanner.error(&format!("unexpected '{}' after derivation", c)))
+    }
+}
+
+// 見出し行とコメントを消す
+// 行番号がずれないように改行は残す
+fn strip(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let line = match line.find("//") {
+                Some(i) => &line[..i],
+                None => line
+            };
+            let trimmed = line.trim();
+            if trimmed.len() > 1 && trimmed.starts_with('*') && trimmed.ends_with('*') {
+                ""
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+struct Scanner {
+    chars: Vec<char>,
+    pos: usize
+}
+
+impl Scanner {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn line(&self) -> usize {
+        self.chars[..self.pos.min(self.chars.len())].iter().filter(|c| **c == '\n').count() + 1
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow::anyhow!("line {}: {}", self.line(), message)
+    }
+
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    // judgment by RULE { premises }
+    fn derivation(&mut self) -> anyhow::Result<Derivation> {
+        self.skip_space();
+        let line = self.line();
+        let start = self.pos;
+        // 判断の中にも { } が出てくる (EvalContML の継続) ので, by RULE { の並びを探す
+        let (end, rule, body) = loop {
+            match self.find_by(self.pos) {
+                Some(found) => break found,
+                None if self.pos >= self.chars.len() => return Err(self.error("'by RULE {' not found")),
+                None => self.pos += 1
+            }
+        };
+        let judgment = normalize(&self.chars[start..end]);
+        if judgment.is_empty() {
+            return Err(self.error("judgment not found"))
+        }
+        self.pos = body;
+
+        let mut premises = vec![];
+        if self.chars[body - 1] == '?' {
+            return Ok(Derivation { judgment, rule, premises, line, hole: true })
+        }
+        loop {
+            self.skip_space();
+            match self.peek() {
+                Some('}') => {
+                    self.pos += 1;
+                    break
+                }
+                Some(';') => self.pos += 1,
+                Some(_) => premises.push(self.derivation()?),
+                None => return Err(self.error(&format!("'}}' not found for '{}' (line {})", rule, line)))
+            }
+        }
+
+        Ok(Derivation { judgment, rule, premises, line, hole: false })
+    }
+
+    // pos から by RULE { (か by RULE ?) が始まっていれば (判断の終わり, 規則名, { の直後) を返す
+    fn find_by(&self, pos: usize) -> Option<(usize, String, usize)> {
+        let at = |i: usize| self.chars.get(i).copied();
+        // by は単語として現れる
+        if pos == 0 || !at(pos - 1)?.is_whitespace() || at(pos)? != 'b' || at(pos + 1)? != 'y' || !at(pos + 2)?.is_whitespace() {
+            return None
+        }
+        let mut i = pos + 2;
+        while at(i)?.is_whitespace() {
+            i += 1;
+        }
+        let rule_start = i;
+        while matches!(at(i), Some(c) if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '?') {
+            i += 1;
+        }
+        if i == rule_start {
+            return None
+        }
+        let rule = self.chars[rule_start..i].iter().collect();
+        while at(i)?.is_whitespace() {
+            i += 1;
+        }
+        match at(i)? {
+            '{' | '?' => Some((pos, rule, i + 1)),
+            _ => None
+        }
+    }
+}
+
+fn normalize(chars: &[char]) -> String {
+    chars.iter().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
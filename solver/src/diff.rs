@@ -0,0 +1,204 @@
+use std::fmt;
+
+use crate::derivation::Derivation;
+use crate::parser::{parse, parse_vars, parse_values, parse_cont, parse_cont_env, parse_store, parse_typing};
+use crate::solver::{self, System};
+use crate::tokenizer::tokenize;
+use crate::types::TypeForm;
+use crate::{nameless, cont, reference, typing};
+
+// 書かれた導出と solver が組み立てた導出の木の差分
+// 前提は判断の主語 (結果の値や型を除いた部分) で対応させ, 対応しないものは位置で対応させる
+pub enum Diff<'d> {
+    // 部分木がまるごと同じ
+    Same(&'d Derivation),
+    // (書かれた導出, solver の導出, 前提の差分)
+    Changed(&'d Derivation, &'d Derivation, Vec<Diff<'d>>),
+    // 書かれた導出にだけある前提
+    Extra(&'d Derivation),
+    // solver の導出にだけある前提
+    Missing(&'d Derivation)
+}
+
+pub fn diff<'d>(submitted: &'d Derivation, reference: &'d Derivation) -> Diff<'d> {
+    if same(submitted, reference) {
+        return Diff::Same(submitted)
+    }
+    Diff::Changed(submitted, reference, diff_premises(&submitted.premises, &reference.premises))
+}
+
+fn diff_premises<'d>(submitted: &'d [Derivation], reference: &'d [Derivation]) -> Vec<Diff<'d>> {
+    let (n, m) = (submitted.len(), reference.len());
+    let matches = |i: usize, j: usize| subject(&submitted[i].judgment) == subject(&reference[j].judgment);
+
+    // 主語が同じ前提の最長共通部分列
+    let mut lcs = vec![vec![0; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if matches(i, j) { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let mut anchors = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if matches(i, j) {
+            anchors.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    anchors.push((n, m));
+
+    // 対応した前提の間に残ったものは位置で対応させ, 余りは Extra か Missing にする
+    let mut diffs = vec![];
+    let (mut i, mut j) = (0, 0);
+    for (ai, aj) in anchors {
+        while i < ai && j < aj {
+            diffs.push(diff(&submitted[i], &reference[j]));
+            i += 1;
+            j += 1;
+        }
+        diffs.extend(submitted[i..ai].iter().map(Diff::Extra));
+        diffs.extend(reference[j..aj].iter().map(Diff::Missing));
+        if ai < n && aj < m {
+            diffs.push(diff(&submitted[ai], &reference[aj]));
+        }
+        i = ai + 1;
+        j = aj + 1;
+    }
+    diffs
+}
+
+fn same(a: &Derivation, b: &Derivation) -> bool {
+    key(&a.judgment) == key(&b.judgment)
+        && a.rule == b.rule
+        && a.premises.len() == b.premises.len()
+        && a.premises.iter().zip(&b.premises).all(|(a, b)| same(a, b))
+}
+
+// 空白の違いと, 空の環境の |- を書くかどうかの違いは無視する
+pub fn key(judgment: &str) -> String {
+    let s = judgment.split_whitespace().collect::<Vec<_>>().join(" ");
+    match s.strip_prefix("|- ") {
+        Some(rest) => rest.to_string(),
+        None => s
+    }
+}
+
+// evalto v, ==> d, : τ, is n を除いた部分
+pub fn subject(judgment: &str) -> String {
+    let s = key(judgment);
+    for sep in [" evalto ", " ==> ", " : ", " is "] {
+        if let Some(i) = s.rfind(sep) {
+            return s[..i].to_string()
+        }
+    }
+    s
+}
+
+// unified diff のように行の先頭に ' ', '-' (書かれた導出), '+' (solver の導出) を付ける
+// 深さごとに二つの空白で字下げする
+impl<'d> fmt::Display for Diff<'d> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_diff(f, self, 0)
+    }
+}
+
+fn write_diff(f: &mut fmt::Formatter, d: &Diff, depth: usize) -> fmt::Result {
+    match d {
+        Diff::Same(d) => write_lines(f, d, ' ', depth),
+        Diff::Extra(d) => write_lines(f, d, '-', depth),
+        Diff::Missing(d) => write_lines(f, d, '+', depth),
+        // 片方に前提がなければ前提どうしを比べられない
+        Diff::Changed(s, r, _) if s.premises.is_empty() || r.premises.is_empty() => {
+            write_lines(f, s, '-', depth)?;
+            write_lines(f, r, '+', depth)
+        }
+        Diff::Changed(s, r, premises) => {
+            let indent = "  ".repeat(depth);
+            if key(&s.judgment) == key(&r.judgment) && s.rule == r.rule {
+                writeln!(f, "  {}{} by {} {{", indent, s.judgment.trim(), s.rule)?;
+            } else {
+                writeln!(f, "- {}{} by {} {{", indent, s.judgment.trim(), s.rule)?;
+                writeln!(f, "+ {}{} by {} {{", indent, r.judgment.trim(), r.rule)?;
+            }
+            for premise in premises {
+                write_diff(f, premise, depth + 1)?;
+            }
+            writeln!(f, "  {}}};", indent)
+        }
+    }
+}
+
+fn write_lines(f: &mut fmt::Formatter, d: &Derivation, mark: char, depth: usize) -> fmt::Result {
+    let indent = "  ".repeat(depth);
+    if d.premises.is_empty() {
+        return writeln!(f, "{} {}{} by {} {{}};", mark, indent, d.judgment.trim(), d.rule)
+    }
+    writeln!(f, "{} {}{} by {} {{", mark, indent, d.judgment.trim(), d.rule)?;
+    for premise in &d.premises {
+        write_lines(f, premise, mark, depth + 1)?;
+    }
+    writeln!(f, "{} {}}};", mark, indent)
+}
+
+// 書かれた導出の結論から solver で導出を組み立てる
+// 結果の値や型は書かれたものを使わずに solver が求める
+pub fn derive_reference(judgment: &str, sys: System) -> anyhow::Result<Derivation> {
+    let tokens = |s: &str| tokenize(s.as_bytes());
+    let split = |sep: &str| judgment.rsplit_once(sep)
+        .map(|(form, _)| form)
+        .ok_or_else(|| anyhow::anyhow!("expected `...{}...` in {:?}: {}", sep, sys, judgment));
+    let env_expr = |form: &'_ str| -> anyhow::Result<(Vec<_>, Vec<_>)> {
+        let (env, expr) = form.split_once("|-").unwrap_or(("", form));
+        Ok((tokens(env)?, tokens(expr)?))
+    };
+
+    match sys {
+        System::EvalML1 | System::EvalML3 | System::EvalML4 | System::EvalML5 => {
+            let (env, expr) = env_expr(split(" evalto ")?)?;
+            solver::derive(&parse(&env, &expr)?, sys)
+        }
+        System::NamelessML3 => {
+            let (vars, expr) = env_expr(split(" ==> ")?)?;
+            nameless::derive(&parse_vars(&vars, &expr)?)
+        }
+        System::EvalNamelessML3 => {
+            let (env, expr) = env_expr(split(" evalto ")?)?;
+            nameless::derive_eval(&parse_values(&env, &expr)?)
+        }
+        System::EvalContML1 => {
+            let (_, expr) = env_expr(split(" evalto ")?)?;
+            cont::derive(&parse_cont(&expr)?)
+        }
+        System::EvalContML4 => {
+            let (env, expr) = env_expr(split(" evalto ")?)?;
+            cont::derive(&parse_cont_env(&env, &expr)?)
+        }
+        System::EvalRefML3 => {
+            let (env, expr) = env_expr(split(" evalto ")?)?;
+            reference::derive(&parse_store(&env, &expr)?)
+        }
+        System::TypingML4 | System::PolyTypingML4 => {
+            let (env, expr) = env_expr(split(" : ")?)?;
+            let TypeForm(env, expr, _) = parse_typing(&env, &expr)?;
+            typing::derive(&TypeForm(env, expr, None), sys)
+        }
+        _ => Err(anyhow::anyhow!("{:?} has no solver; use --check", sys))
+    }
+}
+
+// 型の判断では書かれた型が型変数の決め方に効くので, それも使って導出を組み立てる
+// ほかの体系は derive_reference と同じ
+pub fn derive_claimed(judgment: &str, sys: System) -> anyhow::Result<Derivation> {
+    if !matches!(sys, System::TypingML4 | System::PolyTypingML4) {
+        return derive_reference(judgment, sys)
+    }
+    let (env, expr) = judgment.split_once("|-").unwrap_or(("", judgment));
+    let (env, expr) = (tokenize(env.as_bytes())?, tokenize(expr.as_bytes())?);
+    typing::derive(&parse_typing(&env, &expr)?, sys)
+}
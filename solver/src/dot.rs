@@ -0,0 +1,110 @@
+use std::str::FromStr;
+
+use crate::derivation::Derivation;
+use crate::solver::System;
+
+// Graphviz の向き (rankdir)
+// TB は結論が上, BT は教科書のように結論が下になる
+#[derive(Clone, Copy)]
+pub enum Direction {
+    TopDown,
+    BottomUp,
+    LeftRight
+}
+
+impl FromStr for Direction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tb" => Ok(Direction::TopDown),
+            "bt" => Ok(Direction::BottomUp),
+            "lr" => Ok(Direction::LeftRight),
+            _ => Err(anyhow::anyhow!("unknown direction: {}; expected tb, bt or lr", s))
+        }
+    }
+}
+
+pub struct Graph {
+    pub direction: Direction,
+    // 規則の系統 (E-*, B-*, P-*, T-*) ごとに節点を塗る
+    pub color: bool
+}
+
+impl Default for Graph {
+    fn default() -> Graph {
+        Graph { direction: Direction::TopDown, color: false }
+    }
+}
+
+// 規則の頭の文字ごとの色 (それ以外は灰色)
+// 同じ頭でも体系によって意味が違う (Nat の T-Zero は掛け算, TypingML4 の T-Int は型付け) ので体系ごとに分ける
+const EVAL: &[(&str, &str)] = &[
+    ("E-", "lightblue"),
+    ("B-", "palegreen")
+];
+const PEANO: &[(&str, &str)] = &[
+    ("E-", "lightblue"),
+    ("P-", "lightsalmon"),
+    ("T-", "lightsalmon")
+];
+const TYPING: &[(&str, &str)] = &[
+    ("T-", "plum")
+];
+
+fn families(sys: System) -> &'static [(&'static str, &'static str)] {
+    match sys {
+        System::Nat | System::CompareNat1 | System::CompareNat2 | System::CompareNat3
+            | System::EvalNatExp | System::ReduceNatExp => PEANO,
+        System::TypingML4 | System::PolyTypingML4 => TYPING,
+        _ => EVAL
+    }
+}
+
+// 節点は判断と規則名, 辺は結論から前提へ向ける
+// dot -Tsvg で描ける
+pub fn dot(d: &Derivation, sys: System, graph: &Graph) -> String {
+    let rankdir = match graph.direction {
+        Direction::TopDown => "TB",
+        Direction::BottomUp => "BT",
+        Direction::LeftRight => "LR"
+    };
+    let mut out = String::from("digraph derivation {\n");
+    out.push_str(&format!("  rankdir={};\n", rankdir));
+    // 前提を書いた順に並べる
+    out.push_str("  ordering=out;\n");
+    out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+    let mut count = 0;
+    node(&mut out, d, families(sys), graph, &mut count);
+    out.push_str("}\n");
+    out
+}
+
+// 節点に番号を振って書き, その番号を返す
+fn node(out: &mut String, d: &Derivation, families: &[(&str, &str)], graph: &Graph, count: &mut usize) -> usize {
+    let n = *count;
+    *count += 1;
+    let mut attrs = vec![format!("label=\"{}\\nby {}\"", escape(d.judgment.trim()), escape(&d.rule))];
+    let mut styles = vec![];
+    if d.hole {
+        styles.push("dashed");
+    }
+    if graph.color {
+        let color = families.iter().find(|(prefix, _)| d.rule.starts_with(prefix)).map_or("lightgray", |(_, c)| c);
+        styles.push("filled");
+        attrs.push(format!("fillcolor={}", color));
+    }
+    if !styles.is_empty() {
+        attrs.push(format!("style=\"{}\"", styles.join(",")));
+    }
+    out.push_str(&format!("  n{} [{}];\n", n, attrs.join(", ")));
+    for premise in &d.premises {
+        let m = node(out, premise, families, graph, count);
+        out.push_str(&format!("  n{} -> n{};\n", n, m));
+    }
+    n
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
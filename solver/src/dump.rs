@@ -0,0 +1,55 @@
+use std::fs::File;
+use std::io::{self, Write};
+
+use crate::sexp::Sexp;
+use crate::tokenizer::Token;
+
+// --dump-tokens, --dump-ast, --dump-env で途中の結果を書く
+// 導出の出力と混ざらないように標準エラーか, 指定したファイルに書く
+// 各部分は ; で始まる行を見出しにする
+pub struct Dump {
+    pub tokens: bool,
+    pub ast: bool,
+    pub env: bool,
+    out: Box<dyn Write>
+}
+
+impl Dump {
+    // path があれば作り直して書く (何も書かないときは作らない)
+    pub fn new(tokens: bool, ast: bool, env: bool, path: Option<&str>) -> anyhow::Result<Dump> {
+        let out: Box<dyn Write> = match path {
+            Some(path) if tokens || ast || env => Box::new(File::create(path)?),
+            _ => Box::new(io::stderr())
+        };
+        Ok(Dump { tokens, ast, env, out })
+    }
+
+    // 字句は一行に一つずつ
+    pub fn tokens(&mut self, name: &str, tokens: &[Token]) -> anyhow::Result<()> {
+        if self.tokens {
+            writeln!(self.out, "; tokens: {}", name)?;
+            for token in tokens {
+                writeln!(self.out, "{:?}", token)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn ast(&mut self, ast: Sexp) -> anyhow::Result<()> {
+        if self.ast {
+            writeln!(self.out, "; ast\n{}", ast)?;
+        }
+        Ok(())
+    }
+
+    // EvalRefML3 ではストアと環境の二つ
+    pub fn env(&mut self, env: &[Sexp]) -> anyhow::Result<()> {
+        if self.env {
+            writeln!(self.out, "; env")?;
+            for sexp in env {
+                writeln!(self.out, "{}", sexp)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,29 +1,42 @@
 use std::fmt::{self, Write};
 
 #[derive(Debug, Clone)]
-pub enum Value {
+pub enum Value<'a> {
     Int(isize),
     Bool(bool),
+    // (ε)[fun x -> e]
+    Closure(Env<'a>, &'a String, Box<Expr<'a>>),
+    // (ε)[rec f = fun x -> e]
+    RecClosure(Env<'a>, &'a String, &'a String, Box<Expr<'a>>),
+    Nil,
+    Cons(Box<Value<'a>>, Box<Value<'a>>),
+    // (v1, v2)[fun . -> d]
+    NamelessClosure(NamelessEnv<'a>, Box<Expr<'a>>),
+    // (v1, v2)[rec . = fun . -> d]
+    NamelessRecClosure(NamelessEnv<'a>, Box<Expr<'a>>),
+    // letcc で捕まえた継続 [k]
+    Cont(Cont<'a>),
+    // EvalRefML3 の参照 @l
+    Loc(String)
 }
 
-impl fmt::Display for Value {
+impl<'a> fmt::Display for Value<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
             Value::Int(i) => write!(f, "{}", i),
-            Value::Bool(b) => write!(f, "{}", b)
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
-pub enum Unary<'a> {
-    Minus(Box<Expr<'a>>)
-}
-
-impl<'a> fmt::Display for Unary<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &self {
-            Unary::Minus(i) => write!(f, "-{}", i),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Closure(env, x, e) => write!(f, "({})[fun {} -> {}]", env, x, e),
+            Value::RecClosure(env, fun, x, e) => write!(f, "({})[rec {} = fun {} -> {}]", env, fun, x, e),
+            Value::NamelessClosure(env, d) => write!(f, "({})[fun . -> {}]", env, d),
+            Value::NamelessRecClosure(env, d) => write!(f, "({})[rec . = fun . -> {}]", env, d),
+            Value::Cont(k) => write!(f, "[{}]", k),
+            Value::Loc(l) => write!(f, "@{}", l),
+            Value::Nil => write!(f, "[]"),
+            // :: は右結合なので左側のリストだけ括弧が要る
+            Value::Cons(l, r) => match l.as_ref() {
+                Value::Cons(_, _) => write!(f, "({}) :: {}", l, r),
+                _ => write!(f, "{} :: {}", l, r)
+            }
         }
     }
 }
@@ -36,14 +49,24 @@ pub enum Prim<'a> {
     LessThan(Box<Expr<'a>>, Box<Expr<'a>>)
 }
 
+impl<'a> Prim<'a> {
+    fn fmt_prec(&self, f: &mut fmt::Formatter, tail: bool) -> fmt::Result {
+        // 左結合の演算子は右辺だけ一段強い結合度を要求する
+        let (l, op, r, lprec, rprec) = match &self {
+            Prim::Add(l, r) => (l, "+", r, Expr::ADD, Expr::ADD + 1),
+            Prim::Sub(l, r) => (l, "-", r, Expr::ADD, Expr::ADD + 1),
+            Prim::Mul(l, r) => (l, "*", r, Expr::MUL, Expr::MUL + 1),
+            Prim::LessThan(l, r) => (l, "<", r, Expr::COMPARE + 1, Expr::COMPARE + 1)
+        };
+        l.fmt_prec(f, lprec, false)?;
+        write!(f, " {} ", op)?;
+        r.fmt_prec(f, rprec, tail)
+    }
+}
+
 impl<'a> fmt::Display for Prim<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &self {
-            Prim::Add(l, r) => write!(f, "{} + {}", l, r),
-            Prim::Sub(l, r) => write!(f, "{} - {}", l, r),
-            Prim::Mul(l, r) => write!(f, "{} * {}", l, r),
-            Prim::LessThan(l, r) => write!(f, "{} < {}", l, r)
-        }
+        self.fmt_prec(f, true)
     }
 }
 
@@ -58,27 +81,161 @@ impl<'a> fmt::Display for Prim<'a> {
 #[derive(Debug)]
 pub struct Form<'a>(pub Env<'a>, pub Expr<'a>);
 
+// EvalContML1, EvalContML4
+// 継続 k ::= _ | {フレーム} >> k
+// 先頭が次に使われるフレーム
+// {_ + 5} >> {if _ then 1 else 0} >> _
+#[derive(Debug, Clone)]
+pub struct Cont<'a>(pub Vec<Frame<'a>>);
+
+// 最後の >> _ は省略して書く
+impl<'a> fmt::Display for Cont<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "_")
+        }
+        for (i, frame) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, " >> ")?;
+            }
+            write!(f, "{}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Cont<'a> {
+    pub fn pushed(&self, frame: Frame<'a>) -> Cont<'a> {
+        let mut frames = vec![frame];
+        frames.append(&mut self.0.clone());
+
+        Cont(frames)
+    }
+
+    pub fn rest(&self) -> Cont<'a> {
+        Cont(self.0[1..].to_vec())
+    }
+}
+
+// 穴 (Expr::Hole) を一つだけ含む式
+// EvalContML4 で式を評価するフレームは環境を持つ ({ε |- _ + e})
+// {v + _} のように値だけを持つフレームや EvalContML1 のフレームは環境を持たない
+#[derive(Debug, Clone)]
+pub struct Frame<'a>(pub Option<Env<'a>>, pub Expr<'a>);
+
+impl<'a> fmt::Display for Frame<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            Some(env) if env.0.is_empty() => write!(f, "{{|- {}}}", self.1),
+            Some(env) => write!(f, "{{{} |- {}}}", env, self.1),
+            None => write!(f, "{{{}}}", self.1)
+        }
+    }
+}
+
+// ε |- e >> k / v => k
+// EvalContML1 の判断には環境がない
+#[derive(Debug, Clone)]
+pub enum ContForm<'a> {
+    Eval(Option<Env<'a>>, Expr<'a>, Cont<'a>),
+    Ret(Value<'a>, Cont<'a>)
+}
+
+// NamelessML3
+// x, y |- e
+#[derive(Debug)]
+pub struct TrForm<'a>(pub Vars<'a>, pub Expr<'a>);
+
+// 値を持たない変数の並び (先頭が一番新しい)
+#[derive(Debug, Clone)]
+pub struct Vars<'a>(pub Vec<&'a String>);
+
+impl<'a> fmt::Display for Vars<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, x) in self.0.iter().enumerate().rev() {
+            write!(f, "{}", x)?;
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Vars<'a> {
+    pub fn form(&self) -> String {
+        format!("{} |-", self)
+    }
+
+    pub fn pushed(&self, x: &'a String) -> Vars<'a> {
+        let mut vars = vec![x];
+        vars.append(&mut self.0.clone());
+
+        Vars(vars)
+    }
+}
+
+// EvalNamelessML3
+// 1, true |- d
+#[derive(Debug)]
+pub struct NamelessForm<'a>(pub NamelessEnv<'a>, pub Expr<'a>);
+
+// 値だけの環境 (先頭が一番新しい, #1 で参照される)
+#[derive(Debug, Clone)]
+pub struct NamelessEnv<'a>(pub Vec<Value<'a>>);
+
+impl<'a> fmt::Display for NamelessEnv<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, v) in self.0.iter().enumerate().rev() {
+            write!(f, "{}", v)?;
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> NamelessEnv<'a> {
+    pub fn form(&self) -> String {
+        format!("{} |-", self)
+    }
+
+    pub fn pushed(&self, v: Value<'a>) -> NamelessEnv<'a> {
+        let mut values = vec![v];
+        values.append(&mut self.0.clone());
+
+        NamelessEnv(values)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct EnvVar<'a>(pub &'a String, pub Box<Expr<'a>>);
 
 #[derive(Debug, Clone)]
 pub struct Env<'a>(pub Vec<EnvVar<'a>>);
 
-impl<'a> Env<'a> {
-    pub fn form(&self) -> String {
-        let mut buf = String::new();
+impl<'a> fmt::Display for Env<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for (i, e) in self.0.iter().enumerate().rev() {
-            write!(buf, "{} = {}", e.0, e.1).unwrap();
+            write!(f, "{} = {}", e.0, e.1)?;
             if i != 0 {
-                write!(buf, ", ").unwrap();
+                write!(f, ", ")?;
             }
         }
-        write!(buf, " |-").unwrap();
+        Ok(())
+    }
+}
+
+impl<'a> Env<'a> {
+    pub fn form(&self) -> String {
+        let mut buf = String::new();
+        write!(buf, "{} |-", self).unwrap();
 
         buf
     }
 
-    pub fn appended(&self, other: &'a Env) -> Env {
+    pub fn appended(&self, other: &Env<'a>) -> Env<'a> {
         let mut vars = self.0.clone();
         vars.append(&mut other.0.clone());
 
@@ -86,25 +243,257 @@ impl<'a> Env<'a> {
     }
 }
 
+// EvalRefML3
+// @l = 2, @m = 3 / x = @l |- e
+#[derive(Debug)]
+pub struct RefForm<'a>(pub Store<'a>, pub Env<'a>, pub Expr<'a>);
+
+// 先頭が最初に作られた参照
+#[derive(Debug, Clone)]
+pub struct Store<'a>(pub Vec<(String, Value<'a>)>);
+
+impl<'a> fmt::Display for Store<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (l, v)) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "@{} = {}", l, v)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Store<'a> {
+    // S / ε |- (ストアが空なら ε |-)
+    pub fn form(&self, env: &Env<'a>) -> String {
+        match (self.0.is_empty(), env.0.is_empty()) {
+            (true, _) => env.form(),
+            (false, true) => format!("{} / |-", self),
+            (false, false) => format!("{} / {} |-", self, env)
+        }
+    }
+
+    pub fn get(&self, l: &str) -> Option<&Value<'a>> {
+        self.0.iter().find(|(name, _)| name == l).map(|(_, v)| v)
+    }
+
+    pub fn pushed(&self, l: String, v: Value<'a>) -> Store<'a> {
+        let mut vars = self.0.clone();
+        vars.push((l, v));
+
+        Store(vars)
+    }
+
+    // S[l = v]
+    pub fn updated(&self, l: &str, v: Value<'a>) -> Store<'a> {
+        let vars = self.0.iter()
+            .map(|(name, old)| (name.clone(), if name == l { v.clone() } else { old.clone() }))
+            .collect();
+
+        Store(vars)
+    }
+
+    // 教科書にならって @l, @m, @n, ... の順にまだ使われていない名前を選ぶ
+    pub fn fresh(&self) -> String {
+        let names = "lmnopqrstuvwxyzabcdefghijk".chars().map(|c| c.to_string());
+        let numbered = (1..).map(|i| format!("l{}", i));
+        names.chain(numbered)
+            .find(|l| self.get(l).is_none())
+            .unwrap()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Expr<'a> {
-    Value(Value),
-    Unary(Unary<'a>),
+    Value(Value<'a>),
     Prim(Prim<'a>),
     IfThenElse(Box<Expr<'a>>, Box<Expr<'a>>, Box<Expr<'a>>),
     Ident(&'a String),
-    Let(&'a String, Box<Expr<'a>>, Box<Expr<'a>>)
+    Let(&'a String, Box<Expr<'a>>, Box<Expr<'a>>),
+    Fun(&'a String, Box<Expr<'a>>),
+    App(Box<Expr<'a>>, Box<Expr<'a>>),
+    // let rec f = fun x -> e1 in e2
+    LetRec(&'a String, &'a String, Box<Expr<'a>>, Box<Expr<'a>>),
+    Cons(Box<Expr<'a>>, Box<Expr<'a>>),
+    // match e0 with p1 -> e1 | p2 -> e2 | ...
+    // EvalML4 の match は [] -> e1 | x :: y -> e2 の形に限られる
+    Match(Box<Expr<'a>>, Vec<(Pattern<'a>, Expr<'a>)>),
+    // 以下は NamelessML3 の名前なし式
+    // #n
+    Index(usize),
+    // let . = d1 in d2
+    NamelessLet(Box<Expr<'a>>, Box<Expr<'a>>),
+    // fun . -> d
+    NamelessFun(Box<Expr<'a>>),
+    // let rec . = fun . -> d1 in d2
+    NamelessLetRec(Box<Expr<'a>>, Box<Expr<'a>>),
+    // 継続のフレームの穴 _
+    Hole,
+    // letcc x in e
+    LetCc(&'a String, Box<Expr<'a>>),
+    // 以下は EvalRefML3 の参照
+    // ref e
+    Ref(Box<Expr<'a>>),
+    // !e
+    Deref(Box<Expr<'a>>),
+    // e1 := e2
+    Assign(Box<Expr<'a>>, Box<Expr<'a>>)
 }
 
-impl<'a> fmt::Display for Expr<'a> {
+#[derive(Debug, Clone)]
+pub enum Pattern<'a> {
+    Var(&'a String),
+    Nil,
+    Cons(Box<Pattern<'a>>, Box<Pattern<'a>>),
+    Wild
+}
+
+impl<'a> fmt::Display for Pattern<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            Pattern::Var(x) => write!(f, "{}", x),
+            Pattern::Nil => write!(f, "[]"),
+            Pattern::Cons(l, r) => match l.as_ref() {
+                Pattern::Cons(_, _) => write!(f, "({}) :: {}", l, r),
+                _ => write!(f, "{} :: {}", l, r)
+            },
+            Pattern::Wild => write!(f, "_")
+        }
+    }
+}
+
+impl<'a> Expr<'a> {
+    // 結合度
+    // if, let, fun のように右にどこまでも伸びる式が一番弱い
+    const OPEN: u8 = 0;
+    const ASSIGN: u8 = 1;
+    const COMPARE: u8 = 2;
+    const CONS: u8 = 3;
+    const ADD: u8 = 4;
+    const MUL: u8 = 5;
+    const APP: u8 = 6;
+    const ATOM: u8 = 7;
+
+    fn precedence(&self) -> u8 {
+        match &self {
+            Expr::Value(Value::Int(i)) if *i < 0 => Expr::APP,
+            Expr::Value(Value::Cons(_, _)) | Expr::Cons(_, _) => Expr::CONS,
+            Expr::Value(_) | Expr::Ident(_) | Expr::Index(_) | Expr::Hole | Expr::Deref(_) => Expr::ATOM,
+            Expr::App(_, _) | Expr::Ref(_) => Expr::APP,
+            Expr::Prim(Prim::Mul(_, _)) => Expr::MUL,
+            Expr::Prim(Prim::Add(_, _)) | Expr::Prim(Prim::Sub(_, _)) => Expr::ADD,
+            Expr::Prim(Prim::LessThan(_, _)) => Expr::COMPARE,
+            Expr::Assign(_, _) => Expr::ASSIGN,
+            Expr::IfThenElse(_, _, _) | Expr::Let(_, _, _) | Expr::Fun(_, _) | Expr::LetRec(_, _, _, _) |
+            Expr::Match(_, _) | Expr::LetCc(_, _) | Expr::NamelessLet(_, _) | Expr::NamelessFun(_) | Expr::NamelessLetRec(_, _) => Expr::OPEN
+        }
+    }
+
+    // prec: 周囲が要求する結合度
+    // tail: 式の右端にあるかどうか (右端の if や let は括弧なしで書ける)
+    fn fmt_prec(&self, f: &mut fmt::Formatter, prec: u8, tail: bool) -> fmt::Result {
+        let own = self.precedence();
+        if own < prec && !(own == Expr::OPEN && tail) {
+            write!(f, "(")?;
+            self.fmt_prec(f, Expr::OPEN, true)?;
+            return write!(f, ")")
+        }
+
         match &self {
             Expr::Value(x) => write!(f, "{}", x),
-            Expr::Unary(x) => write!(f, "{}", x),
-            Expr::Prim(x) => write!(f, "{}", x),
-            Expr::IfThenElse(cond, then, els) => write!(f, "if {} then {} else {}", *cond, *then, *els),
+            Expr::Prim(x) => x.fmt_prec(f, tail),
+            Expr::IfThenElse(cond, then, els) => {
+                write!(f, "if {} then {} else ", *cond, *then)?;
+                els.fmt_prec(f, Expr::OPEN, tail)
+            }
             Expr::Ident(name) => write!(f, "{}", name),
-            Expr::Let(var, var_exp, expr) => write!(f, "let {} = {} in {}", var, var_exp, expr)
+            Expr::Let(var, var_exp, expr) => {
+                write!(f, "let {} = {} in ", var, var_exp)?;
+                expr.fmt_prec(f, Expr::OPEN, tail)
+            }
+            Expr::Fun(x, body) => {
+                write!(f, "fun {} -> ", x)?;
+                body.fmt_prec(f, Expr::OPEN, tail)
+            }
+            Expr::App(fun, arg) => {
+                fun.fmt_prec(f, Expr::APP, false)?;
+                write!(f, " ")?;
+                // 引数の fun や let は右端でも括弧が要る
+                arg.fmt_prec(f, Expr::ATOM, false)
+            }
+            Expr::LetRec(fun, x, fun_exp, expr) => {
+                write!(f, "let rec {} = fun {} -> {} in ", fun, x, fun_exp)?;
+                expr.fmt_prec(f, Expr::OPEN, tail)
+            }
+            Expr::Cons(l, r) => {
+                l.fmt_prec(f, Expr::CONS + 1, false)?;
+                write!(f, " :: ")?;
+                r.fmt_prec(f, Expr::CONS, tail)
+            }
+            // 最後以外の節の後ろには | が続くので, そこにある match などは括弧で囲む
+            Expr::Match(e0, clauses) => {
+                write!(f, "match {} with ", e0)?;
+                for (i, (p, e)) in clauses.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{} -> ", p)?;
+                    if i + 1 == clauses.len() {
+                        e.fmt_prec(f, Expr::OPEN, tail)?;
+                    } else {
+                        e.fmt_prec(f, Expr::OPEN + 1, false)?;
+                    }
+                }
+                Ok(())
+            }
+            Expr::Index(n) => write!(f, "#{}", n),
+            Expr::NamelessLet(e1, e2) => {
+                write!(f, "let . = {} in ", e1)?;
+                e2.fmt_prec(f, Expr::OPEN, tail)
+            }
+            Expr::NamelessFun(body) => {
+                write!(f, "fun . -> ")?;
+                body.fmt_prec(f, Expr::OPEN, tail)
+            }
+            Expr::NamelessLetRec(e1, e2) => {
+                write!(f, "let rec . = fun . -> {} in ", e1)?;
+                e2.fmt_prec(f, Expr::OPEN, tail)
+            }
+            Expr::Hole => write!(f, "_"),
+            Expr::LetCc(x, e) => {
+                write!(f, "letcc {} in ", x)?;
+                e.fmt_prec(f, Expr::OPEN, tail)
+            }
+            Expr::Ref(e) => {
+                write!(f, "ref ")?;
+                e.fmt_prec(f, Expr::ATOM, tail)
+            }
+            Expr::Deref(e) => {
+                write!(f, "!")?;
+                e.fmt_prec(f, Expr::ATOM, tail)
+            }
+            // := は右結合
+            Expr::Assign(l, r) => {
+                l.fmt_prec(f, Expr::ASSIGN + 1, false)?;
+                write!(f, " := ")?;
+                r.fmt_prec(f, Expr::ASSIGN, tail)
+            }
         }
     }
+
+    // 環境に書かれた式 (-1 や 1 :: [] など) を値にする
+    pub fn to_value(&self) -> Option<Value<'a>> {
+        match &self {
+            Expr::Value(v) => Some(v.clone()),
+            Expr::Cons(l, r) => Some(Value::Cons(Box::new(l.to_value()?), Box::new(r.to_value()?))),
+            _ => None
+        }
+    }
+}
+
+impl<'a> fmt::Display for Expr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_prec(f, Expr::OPEN, true)
+    }
 }
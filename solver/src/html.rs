@@ -0,0 +1,105 @@
+use crate::derivation::Derivation;
+
+// 一つのファイルで開ける HTML
+// 前提のある導出は <details> で畳めるようにし, 規則名は横に札として付ける
+// 環境は Γ にまとめて, その上に置くと中身が出る
+// 検索欄に書いた文字列は判断の中で印を付け, そこまでの導出を開く
+pub fn html(d: &Derivation) -> String {
+    let mut body = String::new();
+    node(&mut body, d, 0);
+    format!("{}{}{}", HEAD.replace("TITLE", &escape(d.judgment.trim())), body, SCRIPT)
+}
+
+fn node(out: &mut String, d: &Derivation, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let line = format!("{}{}", judgment(d.judgment.trim()), badge(d));
+    if d.premises.is_empty() {
+        out.push_str(&format!("{}<div class=\"leaf\">{}</div>\n", indent, line));
+        return
+    }
+    out.push_str(&format!("{}<details open><summary>{}</summary>\n", indent, line));
+    for premise in &d.premises {
+        node(out, premise, depth + 1);
+    }
+    out.push_str(&format!("{}</details>\n", indent));
+}
+
+// 判断の |- より前が環境
+fn judgment(j: &str) -> String {
+    match j.find("|-") {
+        Some(0) => format!("<span class=\"judgment\">{}</span>", escape(j)),
+        Some(i) => format!(
+            "<span class=\"env\" title=\"{}\">Γ</span> <span class=\"judgment\">{}</span>",
+            escape(j[..i].trim()), escape(&j[i..])
+        ),
+        None => format!("<span class=\"judgment\">{}</span>", escape(j))
+    }
+}
+
+fn badge(d: &Derivation) -> String {
+    if d.hole {
+        format!(" <span class=\"rule hole\">{} ?</span>", escape(&d.rule))
+    } else {
+        format!(" <span class=\"rule\">{}</span>", escape(&d.rule))
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+const HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>TITLE</title>
+<style>
+body { font-family: monospace; margin: 1em; }
+#search { font-family: monospace; width: 30em; margin-bottom: 1em; }
+details, .leaf { margin-left: 1.5em; }
+body > details, body > .leaf { margin-left: 0; }
+summary { cursor: pointer; }
+.leaf { padding-left: 1.1em; }
+.rule { background: #dde6f7; border-radius: 0.6em; padding: 0 0.5em; font-size: 85%; }
+.hole { background: #f7dddd; }
+.env { color: #2a6; cursor: help; border-bottom: 1px dotted; }
+mark { background: #ffe066; }
+</style>
+</head>
+<body>
+<input id="search" type="search" placeholder="search subexpressions">
+"#;
+
+const SCRIPT: &str = r#"<script>
+const judgments = Array.from(document.querySelectorAll(".judgment"));
+judgments.forEach(j => j.dataset.text = j.textContent);
+document.getElementById("search").addEventListener("input", e => {
+  const query = e.target.value;
+  judgments.forEach(j => {
+    const text = j.dataset.text;
+    j.textContent = "";
+    let start = 0;
+    let i = query ? text.indexOf(query) : -1;
+    if (i < 0) {
+      j.textContent = text;
+      return;
+    }
+    while (i >= 0) {
+      j.append(text.slice(start, i));
+      const mark = document.createElement("mark");
+      mark.textContent = query;
+      j.append(mark);
+      start = i + query.length;
+      i = text.indexOf(query, start);
+    }
+    j.append(text.slice(start));
+    // 印の付いた判断が見えるように外側の導出を開く
+    for (let p = j.closest("summary, .leaf").parentElement; p; p = p.parentElement) {
+      if (p.tagName === "DETAILS") p.open = true;
+    }
+  });
+});
+</script>
+</body>
+</html>
+"#;
@@ -0,0 +1,296 @@
+use std::fmt;
+
+// JSON の値
+// 導出に出てくる数は整数だけなので, 数は i64 で持つ
+// オブジェクトは書いた順にキーを並べる
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>)
+}
+
+impl Json {
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    pub fn str(s: &str) -> Json {
+        Json::Str(s.to_string())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None
+        }
+    }
+
+    // 以下は読み込み用で, 形が違えばエラーにする
+    pub fn field(&self, key: &str) -> anyhow::Result<&Json> {
+        self.get(key).ok_or_else(|| anyhow::anyhow!("json: missing field \"{}\" in {}", key, self.brief()))
+    }
+
+    pub fn as_str(&self) -> anyhow::Result<&str> {
+        match self {
+            Json::Str(s) => Ok(s),
+            _ => Err(anyhow::anyhow!("json: expected a string, found {}", self.brief()))
+        }
+    }
+
+    pub fn as_int(&self) -> anyhow::Result<i64> {
+        match self {
+            Json::Int(i) => Ok(*i),
+            _ => Err(anyhow::anyhow!("json: expected an integer, found {}", self.brief()))
+        }
+    }
+
+    pub fn as_bool(&self) -> anyhow::Result<bool> {
+        match self {
+            Json::Bool(b) => Ok(*b),
+            _ => Err(anyhow::anyhow!("json: expected true or false, found {}", self.brief()))
+        }
+    }
+
+    pub fn as_array(&self) -> anyhow::Result<&[Json]> {
+        match self {
+            Json::Array(items) => Ok(items),
+            _ => Err(anyhow::anyhow!("json: expected an array, found {}", self.brief()))
+        }
+    }
+
+    // エラーメッセージ用に一行で短く書く
+    fn brief(&self) -> String {
+        let mut s = String::new();
+        write_json(&mut s, self, None).unwrap();
+        match s.char_indices().nth(60) {
+            Some((i, _)) => format!("{}...", &s[..i]),
+            None => s
+        }
+    }
+}
+
+// 二つの空白で字下げして書く
+// 空の配列とオブジェクトは [] と {} にする
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_json(f, self, Some(0))
+    }
+}
+
+// depth が None なら一行で書く
+fn write_json(f: &mut dyn fmt::Write, json: &Json, depth: Option<usize>) -> fmt::Result {
+    let newline = |f: &mut dyn fmt::Write, depth: Option<usize>| match depth {
+        Some(d) => write!(f, "\n{}", "  ".repeat(d)),
+        None => Ok(())
+    };
+    let inner = depth.map(|d| d + 1);
+    match json {
+        Json::Null => write!(f, "null"),
+        Json::Bool(b) => write!(f, "{}", b),
+        Json::Int(i) => write!(f, "{}", i),
+        Json::Str(s) => write_str(f, s),
+        Json::Array(items) if items.is_empty() => write!(f, "[]"),
+        Json::Object(fields) if fields.is_empty() => write!(f, "{{}}"),
+        Json::Array(items) => {
+            write!(f, "[")?;
+            for (i, item) in items.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ",")?;
+                }
+                newline(f, inner)?;
+                write_json(f, item, inner)?;
+            }
+            newline(f, depth)?;
+            write!(f, "]")
+        }
+        Json::Object(fields) => {
+            write!(f, "{{")?;
+            for (i, (key, value)) in fields.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ",")?;
+                }
+                newline(f, inner)?;
+                write_str(f, key)?;
+                write!(f, ": ")?;
+                write_json(f, value, inner)?;
+            }
+            newline(f, depth)?;
+            write!(f, "}}")
+        }
+    }
+}
+
+fn write_str(f: &mut dyn fmt::Write, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?
+        }
+    }
+    write!(f, "\"")
+}
+
+pub fn parse_json(text: &str) -> anyhow::Result<Json> {
+    let mut reader = Reader { chars: text.chars().collect(), pos: 0 };
+    let json = reader.value()?;
+    reader.skip_space();
+    match reader.peek() {
+        None => Ok(json),
+        Some(c) => Err(reader.error(&format!("unexpected '{}' after value", c)))
+    }
+}
+
+struct Reader {
+    chars: Vec<char>,
+    pos: usize
+}
+
+impl Reader {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        let line = self.chars[..self.pos.min(self.chars.len())].iter().filter(|c| **c == '\n').count() + 1;
+        anyhow::anyhow!("json: line {}: {}", line, message)
+    }
+
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> anyhow::Result<()> {
+        self.skip_space();
+        match self.peek() {
+            Some(d) if d == c => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(d) => Err(self.error(&format!("expected '{}', found '{}'", c, d))),
+            None => Err(self.error(&format!("expected '{}', found end of input", c)))
+        }
+    }
+
+    fn keyword(&mut self, word: &str, json: Json) -> anyhow::Result<Json> {
+        let end = self.pos + word.len();
+        if end <= self.chars.len() && self.chars[self.pos..end].iter().copied().eq(word.chars()) {
+            self.pos = end;
+            return Ok(json)
+        }
+        Err(self.error("unknown literal"))
+    }
+
+    fn value(&mut self) -> anyhow::Result<Json> {
+        self.skip_space();
+        match self.peek() {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::Str(self.string()?)),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                self.skip_space();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items))
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_space();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        _ => break
+                    }
+                }
+                self.expect(']')?;
+                Ok(Json::Array(items))
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut fields = vec![];
+                self.skip_space();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields))
+                }
+                loop {
+                    self.skip_space();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_space();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        _ => break
+                    }
+                }
+                self.expect('}')?;
+                Ok(Json::Object(fields))
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let start = self.pos;
+                self.pos += 1;
+                while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+                if matches!(self.peek(), Some('.' | 'e' | 'E')) {
+                    return Err(self.error("only integers are used in derivations"))
+                }
+                let digits = self.chars[start..self.pos].iter().collect::<String>();
+                digits.parse().map(Json::Int).map_err(|_| self.error(&format!("bad number {}", digits)))
+            }
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+            None => Err(self.error("unexpected end of input"))
+        }
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        if self.peek() != Some('"') {
+            return Err(self.error("expected a string"))
+        }
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let e = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 1;
+                    match e {
+                        '"' | '\\' | '/' => s.push(e),
+                        'n' => s.push('\n'),
+                        't' => s.push('\t'),
+                        'r' => s.push('\r'),
+                        'b' => s.push('\u{8}'),
+                        'f' => s.push('\u{c}'),
+                        'u' => {
+                            let hex = self.chars.get(self.pos..self.pos + 4)
+                                .map(|h| h.iter().collect::<String>())
+                                .ok_or_else(|| self.error("bad \\u escape"))?;
+                            self.pos += 4;
+                            let c = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+                                .ok_or_else(|| self.error(&format!("bad \\u escape \\u{}", hex)))?;
+                            s.push(c);
+                        }
+                        e => return Err(self.error(&format!("unknown escape \\{}", e)))
+                    }
+                }
+                c => s.push(c)
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::derivation::Derivation;
+
+// bussproofs で導出木を書く
+// \usepackage{bussproofs} と, -d-> や \text などのために \usepackage{amsmath} が要る
+//
+// 規則名は横線の右に \RightLabel で付ける
+// bussproofs は前提を三つまでしか並べられないので, 四つ以上の前提は (1), (2), ... と番号だけを並べ,
+// それぞれの前提の導出木は後ろに別の prooftree として書く
+pub fn latex(d: &Derivation) -> String {
+    let mut trees = vec![(None, d)];
+    let mut out = String::new();
+    let mut i = 0;
+    while i < trees.len() {
+        let (number, d) = trees[i];
+        if let Some(n) = number {
+            out.push_str(&format!("\n\\noindent ({})\n", n));
+        }
+        out.push_str("\\begin{prooftree}\n");
+        tree(&mut out, d, &mut trees);
+        out.push_str("\\end{prooftree}\n");
+        i += 1;
+    }
+    out
+}
+
+fn tree<'d>(out: &mut String, d: &'d Derivation, deferred: &mut Vec<(Option<usize>, &'d Derivation)>) {
+    let premises = match (d.hole, d.premises.len()) {
+        (true, _) => {
+            out.push_str("\\AxiomC{$\\vdots$}\n");
+            1
+        }
+        (false, 0) => {
+            out.push_str("\\AxiomC{}\n");
+            1
+        }
+        (false, 1..=3) => {
+            for premise in &d.premises {
+                tree(out, premise, deferred);
+            }
+            d.premises.len()
+        }
+        (false, _) => {
+            let numbers = d.premises.iter().map(|premise| {
+                let n = deferred.len();
+                deferred.push((Some(n), premise));
+                format!("({})", n)
+            }).collect::<Vec<_>>();
+            out.push_str(&format!("\\AxiomC{{{}}}\n", numbers.join(" \\quad ")));
+            1
+        }
+    };
+    let inference = ["UnaryInfC", "BinaryInfC", "TrinaryInfC"][premises - 1];
+    out.push_str(&format!("\\RightLabel{{\\scriptsize {}}}\n", d.rule));
+    out.push_str(&format!("\\{}{{${}$}}\n", inference, math(d.judgment.trim())));
+}
+
+// 記号の並び (長いものから順に調べる)
+const SYMBOLS: &[(&str, &str)] = &[
+    ("--->", "\\longrightarrow"),
+    ("-d->", "\\xrightarrow{d}"),
+    ("-*->", "\\xrightarrow{*}"),
+    ("==>", "\\Longrightarrow"),
+    ("|-", "\\vdash"),
+    ("->", "\\rightarrow"),
+    ("=>", "\\Rightarrow"),
+    ("::", "\\mathbin{::}"),
+    (":=", "\\mathrel{:=}"),
+    (">>", "\\gg"),
+    ("*", "\\ast"),
+    ("_", "\\_"),
+    ("{", "\\{"),
+    ("}", "\\}"),
+    ("#", "\\#"),
+    ("%", "\\%"),
+    ("&", "\\&"),
+    ("$", "\\$"),
+    ("\\", "\\backslash"),
+    (" ", "\\ ")
+];
+
+// 判断を作る英語の語
+// \text で書く (\mathrm では doesn't の ' がプライムになる)
+const KEYWORDS: &[&str] = &["evalto", "is", "plus", "minus", "times", "less", "than", "matches", "doesn't", "when"];
+
+// 判断を数式モードの中身にする
+// 空白はそのまま \ にして, 二文字以上の語 (let など) と Peano 数の S, Z は立体にする
+fn math(judgment: &str) -> String {
+    let mut out = String::new();
+    let mut rest = judgment;
+    'scan: while let Some(c) = rest.chars().next() {
+        for (symbol, tex) in SYMBOLS {
+            if let Some(r) = rest.strip_prefix(symbol) {
+                out.push_str(tex);
+                // \vdash などの後ろに文字が続くと命令の名前とつながる
+                if tex.starts_with('\\') && tex.chars().last().is_some_and(|c| c.is_ascii_alphabetic()) {
+                    out.push(' ');
+                }
+                rest = r;
+                continue 'scan;
+            }
+        }
+        // 'a などの型変数
+        if c == '\'' {
+            let len = 1 + rest[1..].find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len() - 1);
+            out.push_str(&format!("\\mathtt{{{}}}", &rest[..len]));
+            rest = &rest[len..];
+            continue
+        }
+        if c.is_ascii_alphabetic() {
+            let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '\'')).unwrap_or(rest.len());
+            let word = &rest[..len];
+            let peano = word == "Z" || (word == "S" && rest[len..].starts_with('('));
+            if KEYWORDS.contains(&word) {
+                out.push_str(&format!("\\text{{{}}}", word));
+            } else if word.len() > 1 || peano {
+                out.push_str(&format!("\\mathrm{{{}}}", word));
+            } else {
+                out.push_str(word);
+            }
+            rest = &rest[len..];
+            continue
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
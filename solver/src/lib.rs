@@ -1,6 +1,24 @@
-#![feature(box_syntax)]
-
 pub mod tokenizer;
 pub mod expr;
 pub mod parser;
 pub mod solver;
+pub mod nameless;
+pub mod cont;
+pub mod reference;
+pub mod types;
+pub mod typing;
+pub mod derivation;
+pub mod check;
+pub mod nat;
+pub mod diff;
+pub mod complete;
+pub mod query;
+pub mod render;
+pub mod json;
+pub mod schema;
+pub mod latex;
+pub mod dot;
+pub mod html;
+pub mod bar;
+pub mod sexp;
+pub mod dump;
@@ -1,38 +1,254 @@
 use std::env;
+use std::fs;
 
 use solver::tokenizer::tokenize;
-use solver::parser::parse;
-use solver::solver::solve;
+use solver::parser::{parse, parse_vars, parse_values, parse_cont, parse_cont_env, parse_store, parse_typing};
+use solver::solver::{derive, System};
+use solver::nameless;
+use solver::cont;
+use solver::reference;
+use solver::typing;
+use solver::derivation::{parse_derivation, Derivation};
+use solver::check::check;
+use solver::diff::{diff, derive_reference, Diff};
+use solver::complete::complete;
+use solver::query::query;
+use solver::render::{render, Format};
+use solver::json::parse_json;
+use solver::schema::import;
+use solver::expr::ContForm;
+use solver::sexp::{Sexp, expr_sexp, value_sexp, env_sexp, vars_sexp, nameless_env_sexp, store_sexp, type_env_sexp, type_sexp, cont_sexp};
+use solver::dump::Dump;
 
 fn main() -> anyhow::Result<()> {
-    let args = env::args().collect::<Vec<String>>();
+    let mut args = env::args().collect::<Vec<String>>();
 
+    // --system <name> で規則の体系を選ぶ (省略時は EvalML3)
+    let sys = match take_option(&mut args, "--system")? {
+        Some(name) => name.parse::<System>()?,
+        None => System::EvalML3
+    };
+
+    // --format <name> で導出の書き出し方を選ぶ (省略時は solver の出力の形)
+    // copl では --indent <n> で字下げの幅を, --no-semicolon で一番外の ; を省くことを指定できる
+    let mut format = match take_option(&mut args, "--format")? {
+        Some(name) => name.parse::<Format>()?,
+        None => Format::Flat
+    };
+    if let Format::Copl(layout) = &mut format {
+        if let Some(n) = take_option(&mut args, "--indent")? {
+            layout.indent = n.parse()?;
+        }
+        if let Some(i) = args.iter().position(|a| a == "--no-semicolon") {
+            args.remove(i);
+            layout.semicolon = false;
+        }
+    }
+    // dot では --rankdir <tb|bt|lr> で向きを, --color で規則の系統ごとの色付けを指定できる
+    if let Format::Dot(graph) = &mut format {
+        if let Some(d) = take_option(&mut args, "--rankdir")? {
+            graph.direction = d.parse()?;
+        }
+        if let Some(i) = args.iter().position(|a| a == "--color") {
+            args.remove(i);
+            graph.color = true;
+        }
+    }
+    // tree では --width <n> で横に並べる幅の上限を, --ascii で横線を - にすることを指定できる
+    if let Format::Tree(page) = &mut format {
+        if let Some(n) = take_option(&mut args, "--width")? {
+            page.width = n.parse()?;
+        }
+        if let Some(i) = args.iter().position(|a| a == "--ascii") {
+            args.remove(i);
+            page.ascii = true;
+        }
+    }
+
+    // --dump-tokens, --dump-ast, --dump-env で字句, 構文木 (S 式), 環境を標準エラーに書く
+    // --dump-to <file> を付けると標準エラーでなくそのファイルに書く
+    let dump_tokens = take_flag(&mut args, "--dump-tokens");
+    let dump_ast = take_flag(&mut args, "--dump-ast");
+    let dump_env = take_flag(&mut args, "--dump-env");
+    let mut dump = Dump::new(dump_tokens, dump_ast, dump_env, take_option(&mut args, "--dump-to")?.as_deref())?;
+
+    // --parse <file> で書かれた導出を読んで整形し直す
+    if let Some(i) = args.iter().position(|a| a == "--parse") {
+        let path = args.get(i + 1).ok_or_else(|| anyhow::anyhow!("expect file name"))?;
+        let (d, sys) = read_derivation(path, sys)?;
+        print!("{}", render(&d, sys, &format));
+        return Ok(())
+    }
+
+    // --check <file> で書かれた導出を --system の体系で検査する
+    if let Some(i) = args.iter().position(|a| a == "--check") {
+        let path = args.get(i + 1).ok_or_else(|| anyhow::anyhow!("expect file name"))?;
+        let (d, sys) = read_derivation(path, sys)?;
+        check(&d, sys)?;
+        println!("{}: ok", path);
+        return Ok(())
+    }
+
+    // --diff <file> で書かれた導出を solver の導出と比べる
+    if let Some(i) = args.iter().position(|a| a == "--diff") {
+        let path = args.get(i + 1).ok_or_else(|| anyhow::anyhow!("expect file name"))?;
+        let (submitted, sys) = read_derivation(path, sys)?;
+        let reference = derive_reference(&submitted.judgment, sys)?;
+        match diff(&submitted, &reference) {
+            Diff::Same(_) => println!("{}: same as the solver's derivation", path),
+            d => print!("--- {}\n+++ solver\n{}", path, d)
+        }
+        return Ok(())
+    }
+
+    // --complete <file> で書きかけの導出の ? を埋める
+    if let Some(i) = args.iter().position(|a| a == "--complete") {
+        let path = args.get(i + 1).ok_or_else(|| anyhow::anyhow!("expect file name"))?;
+        let (d, sys) = read_derivation(path, sys)?;
+        print!("{}", render(&complete(&d, sys)?, sys, &format));
+        return Ok(())
+    }
+
+    // --query '<judgment>' で判断が導出できるかを答える
+    if let Some(i) = args.iter().position(|a| a == "--query") {
+        let judgment = args.get(i + 1).ok_or_else(|| anyhow::anyhow!("expect judgment"))?;
+        println!("{}", query(judgment, sys)?);
+        return Ok(())
+    }
 
     if args.len() <= 1 {
         println!("usage:");
-        println!("cargo run -- '<env>' '<expr>'\n");
+        println!("cargo run -- [--system <EvalML1..EvalML5>] '<env>' '<expr>'");
+        println!("cargo run -- --system NamelessML3 '<vars>' '<expr>'");
+        println!("cargo run -- --system EvalNamelessML3 '<values>' '<expr>'");
+        println!("cargo run -- --system EvalContML1 '' '<expr> [>> <cont>]'");
+        println!("cargo run -- --system EvalContML4 '<env>' '<expr> [>> <cont>]'");
+        println!("cargo run -- --system EvalRefML3 '[<store> /] <env>' '<expr>'");
+        println!("cargo run -- --system <TypingML4|PolyTypingML4> '<type env>' '<expr> [: <type>]'");
+        println!("cargo run -- --parse <derivation file>");
+        println!("cargo run -- [--system <EvalML1..EvalML3|TypingML4|PolyTypingML4>] --check <derivation file>");
+        println!("cargo run -- --system <Nat|CompareNat1..CompareNat3|EvalNatExp|ReduceNatExp> --check <derivation file>");
+        println!("cargo run -- [--system <name>] --diff <derivation file>");
+        println!("cargo run -- [--system <name>] --complete <derivation file with ?>");
+        println!("cargo run -- [--system <name>] --query '<judgment>'");
+        println!("options: --format <flat|copl|json|latex|dot|html|tree> [--indent <n>] [--no-semicolon] [--rankdir <tb|bt|lr>] [--color]");
+        println!("         [--width <n>] [--ascii]");
+        println!("debug: [--dump-tokens] [--dump-ast] [--dump-env] [--dump-to <file>] (written to stderr unless --dump-to)");
+        println!("derivation files may also be JSON written by --format json\n");
 
         println!("example:");
         println!("cargo run -- 'x = true, y = 4' 'if x then y + 1 else y - 1'");
+        println!("cargo run -- --system NamelessML3 'x, y' 'x + y'");
+        println!("cargo run -- --system EvalNamelessML3 '1, true' 'if #1 then #2 + 1 else 0'");
+        println!("cargo run -- --system EvalContML1 '' '3 >> {{_ + 5}} >> {{_ * 2}}'");
+        println!("cargo run -- --system EvalContML4 '' '1 + letcc k in 2 + k 3'");
+        println!("cargo run -- --system EvalRefML3 '@l = 2 / x = @l' 'x := !x + 1'");
+        println!("cargo run -- --system TypingML4 'x : int' 'fun y -> x + y'");
+        println!("cargo run -- --system PolyTypingML4 \"f : 'a. 'a -> 'a\" 'f f 3'");
+        println!("cargo run -- --query '3 + 4 evalto 8'");
 
         return Ok(())
     }
 
+    if matches!(sys, System::Nat | System::CompareNat1 | System::CompareNat2 | System::CompareNat3 | System::EvalNatExp | System::ReduceNatExp) {
+        return Err(anyhow::anyhow!("{:?} is supported only with --check", sys))
+    }
+
     let env = args.get(1).expect("expect env");
     let expr = args.get(2).expect("expect expression");
-    //println!("* env: {:?} *", env);
-    //println!("* expr: {:?} *", expr);
 
     let env = tokenize(env.as_bytes())?;
     let expr = tokenize(expr.as_bytes())?;
+    dump.tokens("env", &env)?;
+    dump.tokens("expr", &expr)?;
 
-    //println!("{:?}", env);
-    //println!("{:?}", expr);
+    let derivation = match sys {
+        System::NamelessML3 => {
+            let form = parse_vars(env.as_slice(), expr.as_slice())?;
+            dump.env(&[vars_sexp(&form.0)])?;
+            dump.ast(expr_sexp(&form.1))?;
+            nameless::derive(&form)?
+        }
+        System::EvalNamelessML3 => {
+            let form = parse_values(env.as_slice(), expr.as_slice())?;
+            dump.env(&[nameless_env_sexp(&form.0)])?;
+            dump.ast(expr_sexp(&form.1))?;
+            nameless::derive_eval(&form)?
+        }
+        System::EvalContML1 | System::EvalContML4 => {
+            let form = match sys {
+                System::EvalContML1 => parse_cont(expr.as_slice())?,
+                _ => parse_cont_env(env.as_slice(), expr.as_slice())?
+            };
+            // e >> k は (>> e k), v => k は (=> v k) と書く
+            match &form {
+                ContForm::Eval(env, e, k) => {
+                    if let Some(env) = env {
+                        dump.env(&[env_sexp(env)])?;
+                    }
+                    dump.ast(Sexp::List(vec![Sexp::Atom(">>".to_string()), expr_sexp(e), cont_sexp(k)]))?;
+                }
+                ContForm::Ret(v, k) => dump.ast(Sexp::List(vec![Sexp::Atom("=>".to_string()), value_sexp(v), cont_sexp(k)]))?
+            }
+            cont::derive(&form)?
+        }
+        System::EvalRefML3 => {
+            let form = parse_store(env.as_slice(), expr.as_slice())?;
+            dump.env(&[store_sexp(&form.0), env_sexp(&form.1)])?;
+            dump.ast(expr_sexp(&form.2))?;
+            reference::derive(&form)?
+        }
+        System::TypingML4 | System::PolyTypingML4 => {
+            let form = parse_typing(env.as_slice(), expr.as_slice())?;
+            dump.env(&[type_env_sexp(&form.0)])?;
+            // 型を書いたときは (: e t)
+            dump.ast(match &form.2 {
+                Some(t) => Sexp::List(vec![Sexp::Atom(":".to_string()), expr_sexp(&form.1), type_sexp(t)]),
+                None => expr_sexp(&form.1)
+            })?;
+            typing::derive(&form, sys)?
+        }
+        _ => {
+            let form = parse(env.as_slice(), expr.as_slice())?;
+            dump.env(&[env_sexp(&form.0)])?;
+            dump.ast(expr_sexp(&form.1))?;
+            derive(&form, sys)?
+        }
+    };
+    print!("{}", render(&derivation, sys, &format));
 
-    let ast = parse(env.as_slice(), expr.as_slice())?;
-    //println!("{:#?}", ast);
+    Ok(())
+}
 
-    solve(&ast);
+// 導出のファイルを読む
+// JSON (schema.rs) なら体系もファイルに書かれたものを使う
+fn read_derivation(path: &str, sys: System) -> anyhow::Result<(Derivation, System)> {
+    let text = fs::read_to_string(path)?;
+    if text.trim_start().starts_with('{') {
+        return import(&parse_json(&text)?)
+    }
+    Ok((parse_derivation(&text)?, sys))
+}
 
-    Ok(())
+// --name を取り除いて, あったかどうかを返す
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|a| a == name) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false
+    }
+}
+
+// --name <value> を取り除いて value を返す
+fn take_option(args: &mut Vec<String>, name: &str) -> anyhow::Result<Option<String>> {
+    match args.iter().position(|a| a == name) {
+        Some(i) => {
+            let value = args.get(i + 1).ok_or_else(|| anyhow::anyhow!("expect a value after {}", name))?.clone();
+            args.drain(i..i + 2);
+            Ok(Some(value))
+        }
+        None => Ok(None)
+    }
 }
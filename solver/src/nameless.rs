@@ -0,0 +1,240 @@
+use crate::expr::{Expr, Prim, Value, Vars, TrForm, NamelessEnv, NamelessForm};
+use crate::derivation::Derivation;
+
+// BNF
+// NamelessML3
+// X ::= • | X, x
+// d ∈ DBExp ::= i | b | #n | d op d | if d then d else d
+//             | let . = d in d | fun . -> d | d d | let rec . = fun . -> d in d
+//
+// X |- e ==> d
+//
+// EvalNamelessML3
+// v ∈ Value ::= i | b | (V)[fun . -> d] | (V)[rec . = fun . -> d]
+// V ::= • | V, v
+//
+// V |- d evalto v
+
+pub fn solve(form: &TrForm) -> anyhow::Result<()> {
+    print!("{}", derive(form)?);
+    Ok(())
+}
+
+pub fn derive(form: &TrForm) -> anyhow::Result<Derivation> {
+    let vars = &form.0;
+    let expr = &form.1;
+    // 導出を組み立てる前に変換できることを確かめる
+    translate(vars, expr)?;
+    Ok(apply_rule(vars, expr))
+}
+
+fn translate<'a>(vars: &Vars<'a>, expr: &Expr<'a>) -> anyhow::Result<Expr<'a>> {
+    match expr {
+        Expr::Value(v @ (Value::Int(_) | Value::Bool(_))) => Ok(Expr::Value(v.clone())),
+        Expr::Prim(p) => {
+            let prim = match p {
+                Prim::Add(l, r) => Prim::Add(Box::new(translate(vars, l)?), Box::new(translate(vars, r)?)),
+                Prim::Sub(l, r) => Prim::Sub(Box::new(translate(vars, l)?), Box::new(translate(vars, r)?)),
+                Prim::Mul(l, r) => Prim::Mul(Box::new(translate(vars, l)?), Box::new(translate(vars, r)?)),
+                Prim::LessThan(l, r) => Prim::LessThan(Box::new(translate(vars, l)?), Box::new(translate(vars, r)?))
+            };
+            Ok(Expr::Prim(prim))
+        }
+        Expr::IfThenElse(cond, then, els) => {
+            let cond = translate(vars, cond)?;
+            let then = translate(vars, then)?;
+            let els = translate(vars, els)?;
+            Ok(Expr::IfThenElse(Box::new(cond), Box::new(then), Box::new(els)))
+        }
+        Expr::Ident(name) => {
+            match vars.0.iter().position(|x| x == name) {
+                Some(i) => Ok(Expr::Index(i + 1)),
+                None => Err(anyhow::anyhow!("unbound variable: {}", name))
+            }
+        }
+        Expr::Let(x, e1, e2) => {
+            let d1 = translate(vars, e1)?;
+            let d2 = translate(&vars.pushed(x), e2)?;
+            Ok(Expr::NamelessLet(Box::new(d1), Box::new(d2)))
+        }
+        Expr::Fun(x, body) => {
+            let d = translate(&vars.pushed(x), body)?;
+            Ok(Expr::NamelessFun(Box::new(d)))
+        }
+        Expr::App(fun, arg) => {
+            let fun = translate(vars, fun)?;
+            let arg = translate(vars, arg)?;
+            Ok(Expr::App(Box::new(fun), Box::new(arg)))
+        }
+        Expr::LetRec(fun, x, fun_exp, e) => {
+            let d1 = translate(&vars.pushed(fun).pushed(x), fun_exp)?;
+            let d2 = translate(&vars.pushed(fun), e)?;
+            Ok(Expr::NamelessLetRec(Box::new(d1), Box::new(d2)))
+        }
+        _ => Err(anyhow::anyhow!("NamelessML3 does not support: {}", expr))
+    }
+}
+
+// translate が成功した式に対してだけ呼ぶ
+fn apply_rule<'a>(vars: &Vars<'a>, expr: &Expr<'a>) -> Derivation {
+    let translated = translate(vars, expr).expect("internal: translate failed on apply_rule");
+    let judge = format!("{} {} ==> {}", vars.form(), expr, translated);
+    match expr {
+        Expr::Value(Value::Int(_)) => Derivation::new(judge, "Tr-Int", vec![]),
+        Expr::Value(_) => Derivation::new(judge, "Tr-Bool", vec![]),
+        Expr::Prim(p) => {
+            let (l, r, rule) = match p {
+                Prim::Add(l, r) => (l, r, "Tr-Plus"),
+                Prim::Sub(l, r) => (l, r, "Tr-Minus"),
+                Prim::Mul(l, r) => (l, r, "Tr-Times"),
+                Prim::LessThan(l, r) => (l, r, "Tr-Lt")
+            };
+            Derivation::new(judge, rule, vec![apply_rule(vars, l), apply_rule(vars, r)])
+        }
+        Expr::IfThenElse(cond, then, els) => {
+            Derivation::new(judge, "Tr-If", vec![apply_rule(vars, cond), apply_rule(vars, then), apply_rule(vars, els)])
+        }
+        Expr::Ident(name) => {
+            match vars.0.first() {
+                Some(x) if x == name => Derivation::new(judge, "Tr-Var1", vec![]),
+                Some(_) => Derivation::new(judge, "Tr-Var2", vec![apply_rule(&Vars(vars.0[1..].to_vec()), expr)]),
+                None => unreachable!("internal: unbound variable on apply_rule")
+            }
+        }
+        Expr::Let(x, e1, e2) => Derivation::new(judge, "Tr-Let", vec![apply_rule(vars, e1), apply_rule(&vars.pushed(x), e2)]),
+        Expr::Fun(x, body) => Derivation::new(judge, "Tr-Fun", vec![apply_rule(&vars.pushed(x), body)]),
+        Expr::App(fun, arg) => Derivation::new(judge, "Tr-App", vec![apply_rule(vars, fun), apply_rule(vars, arg)]),
+        Expr::LetRec(fun, x, fun_exp, e) => {
+            let premises = vec![apply_rule(&vars.pushed(fun).pushed(x), fun_exp), apply_rule(&vars.pushed(fun), e)];
+            Derivation::new(judge, "Tr-LetRec", premises)
+        }
+        _ => unreachable!("internal: unsupported expression on apply_rule")
+    }
+}
+
+pub fn solve_eval(form: &NamelessForm) -> anyhow::Result<()> {
+    print!("{}", derive_eval(form)?);
+    Ok(())
+}
+
+pub fn derive_eval(form: &NamelessForm) -> anyhow::Result<Derivation> {
+    let env = &form.0;
+    let expr = &form.1;
+    eval(env, expr)?;
+    Ok(apply_eval_rule(env, expr))
+}
+
+fn eval<'a>(env: &NamelessEnv<'a>, expr: &Expr<'a>) -> anyhow::Result<Value<'a>> {
+    match expr {
+        Expr::Value(v @ (Value::Int(_) | Value::Bool(_))) => Ok(v.clone()),
+        Expr::Prim(p) => {
+            let (l, r) = match p {
+                Prim::Add(l, r) | Prim::Sub(l, r) | Prim::Mul(l, r) | Prim::LessThan(l, r) => (l, r)
+            };
+            match (eval(env, l)?, eval(env, r)?) {
+                (Value::Int(l), Value::Int(r)) => Ok(prim_value(p, l, r)),
+                (l, r) => Err(anyhow::anyhow!("expect int: {}, {}", l, r))
+            }
+        }
+        Expr::IfThenElse(cond, then, els) => {
+            match eval(env, cond)? {
+                Value::Bool(true) => eval(env, then),
+                Value::Bool(false) => eval(env, els),
+                v => Err(anyhow::anyhow!("expect bool: {}", v))
+            }
+        }
+        Expr::Index(n) => {
+            match n.checked_sub(1).and_then(|i| env.0.get(i)) {
+                Some(v) => Ok(v.clone()),
+                None => Err(anyhow::anyhow!("unbound index: #{}", n))
+            }
+        }
+        Expr::NamelessLet(e1, e2) => {
+            let v1 = eval(env, e1)?;
+            eval(&env.pushed(v1), e2)
+        }
+        Expr::NamelessFun(body) => Ok(Value::NamelessClosure(env.clone(), body.clone())),
+        Expr::App(fun, arg) => {
+            let closure = eval(env, fun)?;
+            let v = eval(env, arg)?;
+            match app_env(&closure, v) {
+                Some((new_env, body)) => eval(&new_env, &body),
+                None => Err(anyhow::anyhow!("expect closure: {}", closure))
+            }
+        }
+        Expr::NamelessLetRec(fun_exp, e) => eval(&rec_env(env, fun_exp), e),
+        _ => Err(anyhow::anyhow!("EvalNamelessML3 does not support: {}", expr))
+    }
+}
+
+fn prim_value<'a>(p: &Prim<'a>, l: isize, r: isize) -> Value<'a> {
+    match p {
+        Prim::Add(_, _) => Value::Int(l + r),
+        Prim::Sub(_, _) => Value::Int(l - r),
+        Prim::Mul(_, _) => Value::Int(l * r),
+        Prim::LessThan(_, _) => Value::Bool(l < r)
+    }
+}
+
+// E-App: V2, v2
+// E-AppRec: V2, (V2)[rec . = fun . -> d0], v2
+fn app_env<'a>(closure: &Value<'a>, arg: Value<'a>) -> Option<(NamelessEnv<'a>, Expr<'a>)> {
+    match closure {
+        Value::NamelessClosure(env, body) => Some((env.pushed(arg), *body.clone())),
+        Value::NamelessRecClosure(env, body) => Some((env.pushed(closure.clone()).pushed(arg), *body.clone())),
+        _ => None
+    }
+}
+
+fn rec_env<'a>(env: &NamelessEnv<'a>, fun_exp: &Expr<'a>) -> NamelessEnv<'a> {
+    env.pushed(Value::NamelessRecClosure(env.clone(), Box::new(fun_exp.clone())))
+}
+
+// eval が成功した式に対してだけ呼ぶ
+fn apply_eval_rule<'a>(env: &NamelessEnv<'a>, expr: &Expr<'a>) -> Derivation {
+    let evaled = eval(env, expr).expect("internal: eval failed on apply_eval_rule");
+    let judge = format!("{} {} evalto {}", env.form(), expr, evaled);
+    match expr {
+        Expr::Value(Value::Bool(_)) => Derivation::new(judge, "E-Bool", vec![]),
+        Expr::Value(_) => Derivation::new(judge, "E-Int", vec![]),
+        Expr::Prim(p) => {
+            let (l, r, rule, op) = match p {
+                Prim::Add(l, r) => (l, r, "Plus", "plus"),
+                Prim::Sub(l, r) => (l, r, "Minus", "minus"),
+                Prim::Mul(l, r) => (l, r, "Times", "times"),
+                Prim::LessThan(l, r) => (l, r, "Lt", "less than")
+            };
+            let b = format!("{} {} {} is {}", eval(env, l).unwrap(), op, eval(env, r).unwrap(), evaled);
+            let premises = vec![
+                apply_eval_rule(env, l),
+                apply_eval_rule(env, r),
+                Derivation::new(b, &format!("B-{}", rule), vec![])
+            ];
+            Derivation::new(judge, &format!("E-{}", rule), premises)
+        }
+        Expr::IfThenElse(cond, then, els) => {
+            match eval(env, cond) {
+                Ok(Value::Bool(true)) => Derivation::new(judge, "E-IfT", vec![apply_eval_rule(env, cond), apply_eval_rule(env, then)]),
+                _ => Derivation::new(judge, "E-IfF", vec![apply_eval_rule(env, cond), apply_eval_rule(env, els)])
+            }
+        }
+        Expr::Index(_) => Derivation::new(judge, "E-Var", vec![]),
+        Expr::NamelessLet(e1, e2) => {
+            let premises = vec![apply_eval_rule(env, e1), apply_eval_rule(&env.pushed(eval(env, e1).unwrap()), e2)];
+            Derivation::new(judge, "E-Let", premises)
+        }
+        Expr::NamelessFun(_) => Derivation::new(judge, "E-Fun", vec![]),
+        Expr::App(fun, arg) => {
+            let closure = eval(env, fun).unwrap();
+            let rule = match closure {
+                Value::NamelessRecClosure(_, _) => "E-AppRec",
+                _ => "E-App"
+            };
+            let (new_env, body) = app_env(&closure, eval(env, arg).unwrap()).unwrap();
+            let premises = vec![apply_eval_rule(env, fun), apply_eval_rule(env, arg), apply_eval_rule(&new_env, &body)];
+            Derivation::new(judge, rule, premises)
+        }
+        Expr::NamelessLetRec(fun_exp, e) => Derivation::new(judge, "E-LetRec", vec![apply_eval_rule(&rec_env(env, fun_exp), e)]),
+        _ => unreachable!("internal: unsupported expression on apply_eval_rule")
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+// BNF
+// Nat, CompareNat1..3, EvalNatExp, ReduceNatExp
+// n ∈ Nat ::= Z | S(n)
+// e ∈ Exp ::= n | e + e | e * e | (e)
+//
+// ペアノ数は S の数で表す
+#[derive(Debug, Clone, PartialEq)]
+pub enum NatExp {
+    Nat(usize),
+    Plus(Box<NatExp>, Box<NatExp>),
+    Times(Box<NatExp>, Box<NatExp>)
+}
+
+pub struct Nat(pub usize);
+
+impl fmt::Display for Nat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}Z{}", "S(".repeat(self.0), ")".repeat(self.0))
+    }
+}
+
+// + と * は左結合, * の方が強く結合する
+impl fmt::Display for NatExp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NatExp::Nat(n) => write!(f, "{}", Nat(*n)),
+            NatExp::Plus(l, r) => match r.as_ref() {
+                NatExp::Plus(_, _) => write!(f, "{} + ({})", l, r),
+                _ => write!(f, "{} + {}", l, r)
+            },
+            NatExp::Times(l, r) => {
+                match l.as_ref() {
+                    NatExp::Plus(_, _) => write!(f, "({})", l)?,
+                    _ => write!(f, "{}", l)?
+                }
+                match r.as_ref() {
+                    NatExp::Nat(_) => write!(f, " * {}", r),
+                    _ => write!(f, " * ({})", r)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NatJudgment {
+    // n1 plus n2 is n3
+    Plus(usize, usize, usize),
+    // n1 times n2 is n3
+    Times(usize, usize, usize),
+    // n1 is less than n2
+    Less(usize, usize),
+    // e evalto n
+    EvalTo(NatExp, usize),
+    // e ---> e'
+    Reduce(NatExp, NatExp),
+    // e -d-> e'
+    DetReduce(NatExp, NatExp),
+    // e -*-> e'
+    MultiReduce(NatExp, NatExp)
+}
+
+impl fmt::Display for NatJudgment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NatJudgment::Plus(n1, n2, n3) => write!(f, "{} plus {} is {}", Nat(*n1), Nat(*n2), Nat(*n3)),
+            NatJudgment::Times(n1, n2, n3) => write!(f, "{} times {} is {}", Nat(*n1), Nat(*n2), Nat(*n3)),
+            NatJudgment::Less(n1, n2) => write!(f, "{} is less than {}", Nat(*n1), Nat(*n2)),
+            NatJudgment::EvalTo(e, n) => write!(f, "{} evalto {}", e, Nat(*n)),
+            NatJudgment::Reduce(e1, e2) => write!(f, "{} ---> {}", e1, e2),
+            NatJudgment::DetReduce(e1, e2) => write!(f, "{} -d-> {}", e1, e2),
+            NatJudgment::MultiReduce(e1, e2) => write!(f, "{} -*-> {}", e1, e2)
+        }
+    }
+}
+
+// 判断の文字列を読む
+// 項には Z, S, 括弧, +, * しか現れないので, 区切りの語で分けてから項を読む
+pub fn parse_nat_judgment(judgment: &str) -> Result<NatJudgment, String> {
+    let words = |sep: &str| judgment.split_once(sep).map(|(l, r)| (l.trim(), r.trim()));
+
+    if let Some((l, r)) = words(" is less than ") {
+        return Ok(NatJudgment::Less(nat(l)?, nat(r)?))
+    }
+    for (sep, is_plus) in [(" plus ", true), (" times ", false)] {
+        if let Some((n1, rest)) = words(sep) {
+            let (n2, n3) = rest.split_once(" is ")
+                .ok_or_else(|| format!("expected `n1{}n2 is n3`, found `{}`", sep, judgment))?;
+            let (n1, n2, n3) = (nat(n1)?, nat(n2.trim())?, nat(n3.trim())?);
+            return Ok(if is_plus { NatJudgment::Plus(n1, n2, n3) } else { NatJudgment::Times(n1, n2, n3) })
+        }
+    }
+    if let Some((e, n)) = words(" evalto ") {
+        return Ok(NatJudgment::EvalTo(exp(e)?, nat(n)?))
+    }
+    if let Some((e1, e2)) = words("--->") {
+        return Ok(NatJudgment::Reduce(exp(e1)?, exp(e2)?))
+    }
+    if let Some((e1, e2)) = words("-d->") {
+        return Ok(NatJudgment::DetReduce(exp(e1)?, exp(e2)?))
+    }
+    if let Some((e1, e2)) = words("-*->") {
+        return Ok(NatJudgment::MultiReduce(exp(e1)?, exp(e2)?))
+    }
+    Err(format!("unknown judgment `{}`", judgment))
+}
+
+fn nat(s: &str) -> Result<usize, String> {
+    match exp(s)? {
+        NatExp::Nat(n) => Ok(n),
+        e => Err(format!("expected a natural number, found `{}`", e))
+    }
+}
+
+fn exp(s: &str) -> Result<NatExp, String> {
+    let chars = s.chars().filter(|c| !c.is_whitespace()).collect::<Vec<_>>();
+    match plus(&chars)? {
+        (e, []) => Ok(e),
+        (_, rest) => Err(format!("unexpected `{}` in `{}`", rest.iter().collect::<String>(), s))
+    }
+}
+
+fn plus(chars: &[char]) -> Result<(NatExp, &[char]), String> {
+    let (mut e, mut rest) = times(chars)?;
+    while let ['+', r @ ..] = rest {
+        let (e2, r) = times(r)?;
+        e = NatExp::Plus(Box::new(e), Box::new(e2));
+        rest = r;
+    }
+    Ok((e, rest))
+}
+
+fn times(chars: &[char]) -> Result<(NatExp, &[char]), String> {
+    let (mut e, mut rest) = atom(chars)?;
+    while let ['*', r @ ..] = rest {
+        let (e2, r) = atom(r)?;
+        e = NatExp::Times(Box::new(e), Box::new(e2));
+        rest = r;
+    }
+    Ok((e, rest))
+}
+
+// Z | S(n) | (e)
+fn atom(chars: &[char]) -> Result<(NatExp, &[char]), String> {
+    match chars {
+        ['Z', rest @ ..] => Ok((NatExp::Nat(0), rest)),
+        ['S', '(', rest @ ..] => match atom(rest)? {
+            (NatExp::Nat(n), [')', rest @ ..]) => Ok((NatExp::Nat(n + 1), rest)),
+            _ => Err("expected `S(n)`".to_string())
+        },
+        ['(', rest @ ..] => match plus(rest)? {
+            (e, [')', rest @ ..]) => Ok((e, rest)),
+            _ => Err("expected ')'".to_string())
+        },
+        [c, ..] => Err(format!("unexpected '{}'", c)),
+        [] => Err("unexpected end of term".to_string())
+    }
+}
@@ -1,5 +1,13 @@
 use crate::tokenizer::{Token, Operator, Sym};
-use crate::expr::{Expr, Prim, Value, Unary, Form, Env, EnvVar};
+use crate::types::{Type, Scheme, TypeEnv, TypeForm, var_index};
+use crate::expr::{Expr, Prim, Value, Form, Env, EnvVar, Pattern, TrForm, Vars, NamelessForm, NamelessEnv, Cont, ContForm, Frame, RefForm, Store};
+
+// @l = v の並び
+type Locations<'a> = Vec<(String, Value<'a>)>;
+// x : σ の並び
+type Bindings<'a> = Vec<(&'a String, Scheme)>;
+// p -> e の並び
+type Clauses<'a> = Vec<(Pattern<'a>, Expr<'a>)>;
 
 pub fn parse<'a>(env_tokens: &'a [Token], expr_tokens: &'a [Token]) -> anyhow::Result<Form<'a>> {
     let (env, rest1) = parse_env(env_tokens)?;
@@ -10,6 +18,260 @@ pub fn parse<'a>(env_tokens: &'a [Token], expr_tokens: &'a [Token]) -> anyhow::R
     }
 }
 
+// 導出の判断の右辺 (evalto v の v など)
+pub fn parse_value(tokens: &[Token]) -> anyhow::Result<Value<'_>> {
+    let (expr, rest) = expr(tokens)?;
+    match (expr.to_value(), rest) {
+        (Some(v), []) => Ok(v),
+        _ => Err(anyhow::anyhow!("expect value: {}", expr))
+    }
+}
+
+// NamelessML3
+// x, y |- e
+pub fn parse_vars<'a>(var_tokens: &'a [Token], expr_tokens: &'a [Token]) -> anyhow::Result<TrForm<'a>> {
+    let (vars, rest1) = vars(var_tokens)?;
+    let (expr, rest2) = expr(expr_tokens)?;
+    match (rest1, rest2) {
+        ([], []) => Ok(TrForm(Vars(vars), expr)),
+        _ => Err(anyhow::anyhow!("syntax error"))
+    }
+}
+
+// EvalNamelessML3
+// 1, true |- d
+pub fn parse_values<'a>(env_tokens: &'a [Token], expr_tokens: &'a [Token]) -> anyhow::Result<NamelessForm<'a>> {
+    let (values, rest1) = match env_tokens {
+        [] => (vec![], env_tokens),
+        _ => values(env_tokens)?
+    };
+    let (expr, rest2) = expr(expr_tokens)?;
+    match (rest1, rest2) {
+        ([], []) => Ok(NamelessForm(NamelessEnv(values), expr)),
+        _ => Err(anyhow::anyhow!("syntax error"))
+    }
+}
+
+// EvalRefML3
+// @l = 2, @m = 3 / x = @l |- e
+// ストアが空なら '/' ごと省略できる
+pub fn parse_store<'a>(env_tokens: &'a [Token], expr_tokens: &'a [Token]) -> anyhow::Result<RefForm<'a>> {
+    let (store, rest) = match env_tokens {
+        [Token::Loc(_), ..] => match store(env_tokens)? {
+            (store, [Token::Sym(Sym::Slash), rest @ ..]) => (store, rest),
+            _ => return Err(anyhow::anyhow!("'/' not found"))
+        },
+        [Token::Sym(Sym::Slash), rest @ ..] => (vec![], rest),
+        _ => (vec![], env_tokens)
+    };
+    let (env, rest1) = parse_env(rest)?;
+    let (expr, rest2) = expr(expr_tokens)?;
+    match (rest1, rest2) {
+        ([], []) => Ok(RefForm(Store(store), Env(env), expr)),
+        _ => Err(anyhow::anyhow!("syntax error"))
+    }
+}
+
+// evalto v / @l = v, ... の右辺
+// ストアが空なら v だけ
+pub fn parse_ref_result(tokens: &[Token]) -> anyhow::Result<(Value<'_>, Store<'_>)> {
+    let (expr, rest) = expr(tokens)?;
+    let value = expr.to_value().ok_or_else(|| anyhow::anyhow!("expect value: {}", expr))?;
+    let (store, rest) = match rest {
+        [Token::Sym(Sym::Slash), rest @ ..] => store(rest)?,
+        _ => (vec![], rest)
+    };
+    match rest {
+        [] => Ok((value, Store(store))),
+        _ => Err(anyhow::anyhow!("syntax error"))
+    }
+}
+
+// @l = v, ...
+fn store(tokens: &[Token]) -> anyhow::Result<(Locations<'_>, &[Token])> {
+    match tokens {
+        [Token::Loc(l), Token::Op(Operator::Equal), rest @ ..] => {
+            let (expr, rest) = expr(rest)?;
+            let value = expr.to_value().ok_or_else(|| anyhow::anyhow!("expect value: {}", expr))?;
+            let (mut store, rest) = match rest {
+                [Token::Sym(Sym::Comma), rest @ ..] => store(rest)?,
+                _ => (vec![], rest)
+            };
+            store.insert(0, (l.clone(), value));
+
+            Ok((store, rest))
+        }
+        _ => Err(anyhow::anyhow!("invalid store"))
+    }
+}
+
+// TypingML4, PolyTypingML4
+// x : int, f : 'a. 'a -> 'a |- e : t
+// : t を省略したときは型を推論する
+pub fn parse_typing<'a>(env_tokens: &'a [Token], expr_tokens: &'a [Token]) -> anyhow::Result<TypeForm<'a>> {
+    let (env, rest1) = type_env(env_tokens)?;
+    let (expr, rest2) = expr(expr_tokens)?;
+    let (t, rest2) = match rest2 {
+        [Token::Sym(Sym::Colon), rest @ ..] => {
+            let (t, rest) = ty(rest)?;
+            (Some(t), rest)
+        }
+        _ => (None, rest2)
+    };
+    match (rest1, rest2) {
+        ([], []) => Ok(TypeForm(TypeEnv(env), expr, t)),
+        _ => Err(anyhow::anyhow!("syntax error"))
+    }
+}
+
+fn type_env(tokens: &[Token]) -> anyhow::Result<(Bindings<'_>, &[Token])> {
+    match tokens {
+        [Token::Var(name), Token::Sym(Sym::Colon), rest @ ..] => {
+            let (t, rest) = scheme(rest)?;
+            let (mut env, rest) = match rest {
+                [Token::Sym(Sym::Comma), rest @ ..] => type_env(rest)?,
+                _ => (vec![], rest)
+            };
+            env.push((name, t));
+
+            Ok((env, rest))
+        }
+        _ => Ok((vec![], tokens))
+    }
+}
+
+// 'a 'b. t
+// 量化がなければただの型
+fn scheme(tokens: &[Token]) -> anyhow::Result<(Scheme, &[Token])> {
+    let mut vars = vec![];
+    let mut rest = tokens;
+    while let [Token::TyVar(a), rest1 @ ..] = rest {
+        vars.push(ty_var(a)?);
+        rest = rest1;
+    }
+    match rest {
+        [Token::Sym(Sym::Dot), rest @ ..] if !vars.is_empty() => {
+            let (t, rest) = ty(rest)?;
+            Ok((Scheme(vars, t), rest))
+        }
+        _ => {
+            let (t, rest) = ty(tokens)?;
+            Ok((Scheme(vec![], t), rest))
+        }
+    }
+}
+
+fn ty_var(name: &str) -> anyhow::Result<usize> {
+    var_index(name).ok_or_else(|| anyhow::anyhow!("invalid type variable: '{}", name))
+}
+
+// t1 -> t2
+// 右結合
+fn ty(tokens: &[Token]) -> anyhow::Result<(Type, &[Token])> {
+    let (left, rest) = list_ty(tokens)?;
+    match rest {
+        [Token::Sym(Sym::Arrow), rest @ ..] => {
+            let (right, rest) = ty(rest)?;
+            Ok((Type::Fun(Box::new(left), Box::new(right)), rest))
+        }
+        _ => Ok((left, rest))
+    }
+}
+
+// t list list
+fn list_ty(tokens: &[Token]) -> anyhow::Result<(Type, &[Token])> {
+    let (mut t, mut rest) = match tokens {
+        [Token::Var(name), rest @ ..] if name == "int" => (Type::Int, rest),
+        [Token::Var(name), rest @ ..] if name == "bool" => (Type::Bool, rest),
+        [Token::TyVar(a), rest @ ..] => (Type::Var(ty_var(a)?), rest),
+        [Token::Sym(Sym::LParen), rest @ ..] => match ty(rest)? {
+            (t, [Token::Sym(Sym::RParen), rest @ ..]) => (t, rest),
+            _ => return Err(anyhow::anyhow!("')' not found"))
+        },
+        _ => return Err(anyhow::anyhow!("invalid type"))
+    };
+    while let [Token::Var(name), rest1 @ ..] = rest {
+        if name != "list" {
+            break
+        }
+        t = Type::List(Box::new(t));
+        rest = rest1;
+    }
+
+    Ok((t, rest))
+}
+
+// EvalContML1
+// e >> k / v => k
+// >> k を省略したときは k = _
+pub fn parse_cont(tokens: &[Token]) -> anyhow::Result<ContForm<'_>> {
+    cont_form(None, tokens)
+}
+
+// EvalContML4
+// ε |- e >> k / v => k
+pub fn parse_cont_env<'a>(env_tokens: &'a [Token], expr_tokens: &'a [Token]) -> anyhow::Result<ContForm<'a>> {
+    match parse_env(env_tokens)? {
+        (env, []) => cont_form(Some(Env(env)), expr_tokens),
+        _ => Err(anyhow::anyhow!("syntax error"))
+    }
+}
+
+fn cont_form<'a>(env: Option<Env<'a>>, tokens: &'a [Token]) -> anyhow::Result<ContForm<'a>> {
+    let (expr, rest) = expr(tokens)?;
+    let (form, rest) = match rest {
+        [Token::Sym(Sym::RetArrow), rest @ ..] => {
+            let value = expr.to_value().ok_or_else(|| anyhow::anyhow!("expect value: {}", expr))?;
+            let (k, rest) = cont(rest)?;
+            (ContForm::Ret(value, k), rest)
+        }
+        [Token::Sym(Sym::ContArrow), rest @ ..] => {
+            let (k, rest) = cont(rest)?;
+            (ContForm::Eval(env, expr, k), rest)
+        }
+        _ => (ContForm::Eval(env, expr, Cont(vec![])), rest)
+    };
+    match rest {
+        [] => Ok(form),
+        _ => Err(anyhow::anyhow!("syntax error"))
+    }
+}
+
+// _ | {frame} | {frame} >> k
+fn cont(tokens: &[Token]) -> anyhow::Result<(Cont<'_>, &[Token])> {
+    match tokens {
+        [Token::Var(x), rest @ ..] if x == "_" => Ok((Cont(vec![]), rest)),
+        [Token::Sym(Sym::LBrace), rest @ ..] => {
+            let (frame, rest) = frame(rest)?;
+            let rest = match rest {
+                [Token::Sym(Sym::RBrace), rest @ ..] => rest,
+                _ => return Err(anyhow::anyhow!("'}}' not found"))
+            };
+            let (k, rest) = match rest {
+                [Token::Sym(Sym::ContArrow), rest @ ..] => cont(rest)?,
+                _ => (Cont(vec![]), rest)
+            };
+            Ok((k.pushed(frame), rest))
+        }
+        _ => Err(anyhow::anyhow!("invalid continuation"))
+    }
+}
+
+// ε |- e / e
+fn frame(tokens: &[Token]) -> anyhow::Result<(Frame<'_>, &[Token])> {
+    let (env, rest) = match tokens {
+        [Token::Sym(Sym::Turnstile), ..] | [Token::Var(_), Token::Op(Operator::Equal), ..] => {
+            match parse_env(tokens)? {
+                (env, [Token::Sym(Sym::Turnstile), rest @ ..]) => (Some(Env(env)), rest),
+                _ => return Err(anyhow::anyhow!("'|-' not found"))
+            }
+        }
+        _ => (None, tokens)
+    };
+    let (expr, rest) = expr(rest)?;
+    Ok((Frame(env, expr), rest))
+}
+
 //fn form(env_tokens: &[Token], expr_tokens: &[Token]) -> anyhow::Result<(Form, &[Token])> {
 //    let (env, rest) = env(env_tokens)?;
 //    let (exp, rest) = expr(rest)?;
@@ -17,34 +279,79 @@ pub fn parse<'a>(env_tokens: &'a [Token], expr_tokens: &'a [Token]) -> anyhow::R
 //    Ok((Form(Env(env), exp), rest))
 //}
 
-fn parse_env(tokens: &[Token]) -> anyhow::Result<(Vec<EnvVar>, &[Token])> {
+fn parse_env(tokens: &[Token]) -> anyhow::Result<(Vec<EnvVar<'_>>, &[Token])> {
     match tokens {
         [Token::Var(name), Token::Op(Operator::Equal), rest @ ..] => {
             let (expr, rest) = expr(rest)?;
-            println!("env rest: {:?}", rest);
+            let expr = match expr.to_value() {
+                Some(v) => Expr::Value(v),
+                None => expr
+            };
             let (mut env, rest) = parse_env(rest)?;
-            let mut cur = vec![EnvVar(name, box expr)];
+            let mut cur = vec![EnvVar(name, Box::new(expr))];
             env.append(&mut cur);
 
             Ok((env, rest))
         }
         [Token::Sym(Sym::Comma), rest @ ..] => parse_env(rest),
-        [] => Ok((vec![], tokens)),
+        // クロージャの環境は ')' で, 継続のフレームの環境は '|-' で終わる
+        [] | [Token::Sym(Sym::RParen), ..] | [Token::Sym(Sym::Turnstile), ..] => Ok((vec![], tokens)),
         _ => Err(anyhow::anyhow!("internal: unexpected token at env"))
     }
 }
 
-fn expr(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
-    op_compare(tokens)
+fn vars(tokens: &[Token]) -> anyhow::Result<(Vec<&String>, &[Token])> {
+    match tokens {
+        [Token::Var(name), rest @ ..] => {
+            let (mut vars, rest) = vars(rest)?;
+            vars.push(name);
+
+            Ok((vars, rest))
+        }
+        [Token::Sym(Sym::Comma), rest @ ..] => vars(rest),
+        _ => Ok((vec![], tokens))
+    }
+}
+
+// v1, v2, ...
+fn values(tokens: &[Token]) -> anyhow::Result<(Vec<Value<'_>>, &[Token])> {
+    let (expr, rest) = expr(tokens)?;
+    let value = expr.to_value().ok_or_else(|| anyhow::anyhow!("expect value: {}", expr))?;
+    match rest {
+        [Token::Sym(Sym::Comma), rest @ ..] => {
+            let (mut values, rest) = values(rest)?;
+            values.push(value);
+
+            Ok((values, rest))
+        }
+        _ => Ok((vec![value], rest))
+    }
+}
+
+fn expr(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
+    op_assign(tokens)
+}
+
+// :=
+// 右結合
+fn op_assign(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
+    let (left, rest) = op_compare(tokens)?;
+    match rest {
+        [Token::Sym(Sym::Assign), rest1 @ ..] => {
+            let (right, rest2) = op_assign(rest1)?;
+            Ok((Expr::Assign(Box::new(left), Box::new(right)), rest2))
+        }
+        _ => Ok((left, rest))
+    }
 }
 
-fn op_compare(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
-    let (mut left, mut rest) = op_arith1(tokens)?;
+fn op_compare(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
+    let (mut left, mut rest) = op_cons(tokens)?;
     while !rest.is_empty() {
         match rest {
             [Token::Op(Operator::LessThan), rest1 @ ..] => {
                 let (right, rest2) = op_compare(rest1)?;
-                left = Expr::Prim(Prim::LessThan(box left, box right));
+                left = Expr::Prim(Prim::LessThan(Box::new(left), Box::new(right)));
                 rest = rest2;
             }
             _ => return Ok((left, rest))
@@ -53,20 +360,33 @@ fn op_compare(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
     Ok((left, rest))
 }
 
+// ::
+// 右結合
+fn op_cons(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
+    let (left, rest) = op_arith1(tokens)?;
+    match rest {
+        [Token::Sym(Sym::Cons), rest1 @ ..] => {
+            let (right, rest2) = op_cons(rest1)?;
+            Ok((Expr::Cons(Box::new(left), Box::new(right)), rest2))
+        }
+        _ => Ok((left, rest))
+    }
+}
+
 // 結合度が低いもの
 // +, -
-fn op_arith1(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
+fn op_arith1(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
     let (mut left, mut rest) = op_arith2(tokens)?;
     while !rest.is_empty() {
         match rest {
             [Token::Op(Operator::Plus), rest1 @ ..] => {
                 let (right, rest2) = op_arith2(rest1)?;
-                left = Expr::Prim(Prim::Add(box left, box right));
+                left = Expr::Prim(Prim::Add(Box::new(left), Box::new(right)));
                 rest = rest2;
             }
             [Token::Op(Operator::Minus), rest1 @ ..] => {
                 let (right, rest2) = op_arith2(rest1)?;
-                left = Expr::Prim(Prim::Sub(box left, box right));
+                left = Expr::Prim(Prim::Sub(Box::new(left), Box::new(right)));
                 rest = rest2;
             } // +, - でなければそこでarith1の式が完了している
             _ => return Ok((left, rest))
@@ -76,13 +396,13 @@ fn op_arith1(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
 }
 // 結合度が高いもの
 // *
-fn op_arith2(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
+fn op_arith2(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
     let (mut left, mut rest) = unary(tokens)?;
     while !rest.is_empty() {
         match rest {
             [Token::Op(Operator::Mul), rest1 @ ..] => {
                 let (right, rest2) = unary(rest1)?;
-                left = Expr::Prim(Prim::Mul(box left, box right));
+                left = Expr::Prim(Prim::Mul(Box::new(left), Box::new(right)));
                 rest = rest2;
             },
             _ => return Ok((left, rest))
@@ -92,43 +412,93 @@ fn op_arith2(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
     Ok((left, rest))
 }
 
-fn unary(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
+fn unary(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
     match tokens {
-        [Token::Op(Operator::Minus), rest @ ..] => {
-            let (value, rest1) = value(rest)?;
-            Ok((Expr::Unary(Unary::Minus(box value)), rest1))
+        // -3 は負の整数の定数
+        [Token::Op(Operator::Minus), Token::Int(i), rest @ ..] => Ok((Expr::Value(Value::Int(-i)), rest)),
+        [Token::Op(Operator::Minus), ..] => Err(anyhow::anyhow!("unary minus is only allowed before an integer literal")),
+        _ => app(tokens)
+    }
+}
+
+// 関数適用 (左結合)
+// 引数になれるのは括弧なしで書ける式だけ
+// ref e も関数適用と同じ結合度
+fn app(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
+    let (mut left, mut rest) = match tokens {
+        [Token::Ref, rest @ ..] => {
+            let (e, rest) = value(rest)?;
+            (Expr::Ref(Box::new(e)), rest)
         }
-        _ => value(tokens)
+        _ => value(tokens)?
+    };
+    while let [Token::Int(_) | Token::Bool(_) | Token::Var(_) | Token::Index(_) | Token::Loc(_) |
+               Token::Sym(Sym::LParen | Sym::LBracket | Sym::Bang), ..] = rest {
+        let (right, rest1) = value(rest)?;
+        left = Expr::App(Box::new(left), Box::new(right));
+        rest = rest1;
     }
+
+    Ok((left, rest))
 }
 
-fn value(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
+fn value(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
     match tokens {
         [Token::Int(i), rest @ ..] => Ok((Expr::Value(Value::Int(*i)), rest)),
         [Token::Bool(i), rest @ ..] => Ok((Expr::Value(Value::Bool(*i)), rest)),
+        [Token::Loc(l), rest @ ..] => Ok((Expr::Value(Value::Loc(l.clone())), rest)),
+        [Token::Sym(Sym::Bang), rest @ ..] => {
+            let (e, rest) = value(rest)?;
+            Ok((Expr::Deref(Box::new(e)), rest))
+        }
+        [Token::Sym(Sym::LBracket), Token::Sym(Sym::RBracket), rest @ ..] => Ok((Expr::Value(Value::Nil), rest)),
+        [Token::Sym(Sym::LBracket), rest @ ..] => {
+            // [k]
+            let (k, rest) = cont(rest)?;
+            match rest {
+                [Token::Sym(Sym::RBracket), rest @ ..] => Ok((Expr::Value(Value::Cont(k)), rest)),
+                _ => Err(anyhow::anyhow!("']' not found"))
+            }
+        }
+        [Token::Sym(Sym::LParen), Token::Sym(Sym::RParen), Token::Sym(Sym::LBracket), Token::Fun | Token::Rec, Token::Sym(Sym::Dot), ..] =>
+            nameless_closure(tokens),
+        [Token::Sym(Sym::LParen), Token::Sym(Sym::RParen), ..] |
+        [Token::Sym(Sym::LParen), Token::Var(_), Token::Op(Operator::Equal), ..] => closure(tokens),
         [Token::Sym(Sym::LParen), ..] => paren_expr(tokens),
         [Token::If, ..] => if_then_else(tokens),
+        [Token::Var(x), rest @ ..] if x == "_" => Ok((Expr::Hole, rest)),
         [Token::Var(x), rest @ ..] => Ok((Expr::Ident(x), rest)),
+        [Token::Index(n), rest @ ..] => Ok((Expr::Index(*n), rest)),
+        [Token::Let, Token::Rec, ..] => let_rec_in(tokens),
         [Token::Let, ..] => let_in(tokens),
-        _ => unimplemented!("unsupported token")
+        [Token::Fun, ..] => fun(tokens),
+        [Token::Match, ..] => match_with(tokens),
+        [Token::LetCc, Token::Var(x), Token::In, rest @ ..] => {
+            let (e, rest) = expr(rest)?;
+            Ok((Expr::LetCc(x, Box::new(e)), rest))
+        }
+        [t, ..] => Err(anyhow::anyhow!("unexpected token {:?}", t)),
+        [] => Err(anyhow::anyhow!("unexpected end of input"))
     }
 }
 
-fn paren_expr(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
+fn paren_expr(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
     match tokens {
         [Token::Sym(Sym::LParen), rest @ ..] => {
             let (expr, rest1) = expr(rest)?;
-            if let [Token::Sym(Sym::RParen), rest2 @ ..] = rest1 {
-                Ok((expr, rest2))
-            } else {
-                Err(anyhow::anyhow!("')' not found"))
+            match rest1 {
+                // (1, true)[fun . -> #1] のような名前なしクロージャ
+                [Token::Sym(Sym::Comma), ..] |
+                [Token::Sym(Sym::RParen), Token::Sym(Sym::LBracket), Token::Fun | Token::Rec, ..] => nameless_closure(tokens),
+                [Token::Sym(Sym::RParen), rest2 @ ..] => Ok((expr, rest2)),
+                _ => Err(anyhow::anyhow!("')' not found"))
             }
         }
         _ => Err(anyhow::anyhow!("internal: unexpected invoke paren_expr"))
     }
 }
 
-fn if_then_else(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
+fn if_then_else(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
     match tokens {
         [Token::If, rest @ ..] => {
             let (cond, rest1) = expr(rest)?;
@@ -136,7 +506,7 @@ fn if_then_else(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
                 let (then, rest3) = expr(rest2)?;
                 if let [Token::Else, rest4 @ ..] = rest3 {
                     let (els, rest5) = expr(rest4)?;
-                    Ok((Expr::IfThenElse(box cond, box then, box els), rest5))
+                    Ok((Expr::IfThenElse(Box::new(cond), Box::new(then), Box::new(els)), rest5))
                 } else {
                     Err(anyhow::anyhow!("else section not found"))
                 }
@@ -148,17 +518,201 @@ fn if_then_else(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
     }
 }
 
-fn let_in(tokens: &[Token]) -> anyhow::Result<(Expr, &[Token])> {
+fn let_in(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
     match tokens {
+        [Token::Let, Token::Sym(Sym::Dot), Token::Op(Operator::Equal), rest @ ..] => {
+            let (e1, rest) = expr(rest)?;
+            let (e2, rest) =
+                match rest {
+                    [Token::In, rest @ ..] => expr(rest)?,
+                    _ => return Err(anyhow::anyhow!("in section not found"))
+                };
+            Ok((Expr::NamelessLet(Box::new(e1), Box::new(e2)), rest))
+        }
         [Token::Let, Token::Var(x), Token::Op(Operator::Equal), rest @ ..] => {
             let (var_exp, rest) = expr(rest)?;
             let (exp, rest) =
                 match rest {
                     [Token::In, rest @ ..] => expr(rest)?,
-                    _ => unreachable!("parser: unreachable point at in on let_in")
+                    _ => return Err(anyhow::anyhow!("in section not found"))
+                };
+            Ok((Expr::Let(x, Box::new(var_exp), Box::new(exp)), rest))
+        }
+        _ => Err(anyhow::anyhow!("invalid let: expected `let x = e in e`"))
+    }
+}
+
+fn fun(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
+    match tokens {
+        [Token::Fun, Token::Var(x), Token::Sym(Sym::Arrow), rest @ ..] => {
+            let (body, rest) = expr(rest)?;
+            Ok((Expr::Fun(x, Box::new(body)), rest))
+        }
+        [Token::Fun, Token::Sym(Sym::Dot), Token::Sym(Sym::Arrow), rest @ ..] => {
+            let (body, rest) = expr(rest)?;
+            Ok((Expr::NamelessFun(Box::new(body)), rest))
+        }
+        _ => Err(anyhow::anyhow!("invalid fun expression"))
+    }
+}
+
+fn let_rec_in(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
+    match tokens {
+        [Token::Let, Token::Rec, Token::Var(f), Token::Op(Operator::Equal),
+         Token::Fun, Token::Var(x), Token::Sym(Sym::Arrow), rest @ ..] => {
+            let (fun_exp, rest) = expr(rest)?;
+            let (exp, rest) =
+                match rest {
+                    [Token::In, rest @ ..] => expr(rest)?,
+                    _ => return Err(anyhow::anyhow!("in section not found"))
                 };
-            Ok((Expr::Let(x, box var_exp, box exp), rest))
+            Ok((Expr::LetRec(f, x, Box::new(fun_exp), Box::new(exp)), rest))
+        }
+        [Token::Let, Token::Rec, Token::Sym(Sym::Dot), Token::Op(Operator::Equal),
+         Token::Fun, Token::Sym(Sym::Dot), Token::Sym(Sym::Arrow), rest @ ..] => {
+            let (e1, rest) = expr(rest)?;
+            let (e2, rest) =
+                match rest {
+                    [Token::In, rest @ ..] => expr(rest)?,
+                    _ => return Err(anyhow::anyhow!("in section not found"))
+                };
+            Ok((Expr::NamelessLetRec(Box::new(e1), Box::new(e2)), rest))
+        }
+        _ => Err(anyhow::anyhow!("invalid let rec expression"))
+    }
+}
+
+// (x = 1, y = 2)[fun z -> x + y + z]
+// ()[rec f = fun x -> f x]
+fn closure(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
+    match tokens {
+        [Token::Sym(Sym::LParen), rest @ ..] => {
+            let (env, rest) = parse_env(rest)?;
+            let env = Env(env);
+            match rest {
+                [Token::Sym(Sym::RParen), Token::Sym(Sym::LBracket),
+                 Token::Fun, Token::Var(x), Token::Sym(Sym::Arrow), rest @ ..] => {
+                    let (body, rest) = expr(rest)?;
+                    match rest {
+                        [Token::Sym(Sym::RBracket), rest @ ..] =>
+                            Ok((Expr::Value(Value::Closure(env, x, Box::new(body))), rest)),
+                        _ => Err(anyhow::anyhow!("']' not found"))
+                    }
+                }
+                [Token::Sym(Sym::RParen), Token::Sym(Sym::LBracket),
+                 Token::Rec, Token::Var(f), Token::Op(Operator::Equal),
+                 Token::Fun, Token::Var(x), Token::Sym(Sym::Arrow), rest @ ..] => {
+                    let (body, rest) = expr(rest)?;
+                    match rest {
+                        [Token::Sym(Sym::RBracket), rest @ ..] =>
+                            Ok((Expr::Value(Value::RecClosure(env, f, x, Box::new(body))), rest)),
+                        _ => Err(anyhow::anyhow!("']' not found"))
+                    }
+                }
+                _ => Err(anyhow::anyhow!("invalid closure"))
+            }
+        }
+        _ => Err(anyhow::anyhow!("internal: unexpected invoke closure"))
+    }
+}
+
+// (1, true)[fun . -> #1]
+// ()[rec . = fun . -> #2 #1]
+fn nameless_closure(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
+    let (env, rest) = match tokens {
+        [Token::Sym(Sym::LParen), Token::Sym(Sym::RParen), ..] => (vec![], &tokens[1..]),
+        [Token::Sym(Sym::LParen), rest @ ..] => values(rest)?,
+        _ => return Err(anyhow::anyhow!("internal: unexpected invoke nameless_closure"))
+    };
+    let env = NamelessEnv(env);
+    let (value, rest) = match rest {
+        [Token::Sym(Sym::RParen), Token::Sym(Sym::LBracket),
+         Token::Fun, Token::Sym(Sym::Dot), Token::Sym(Sym::Arrow), rest @ ..] => {
+            let (body, rest) = expr(rest)?;
+            (Value::NamelessClosure(env, Box::new(body)), rest)
+        }
+        [Token::Sym(Sym::RParen), Token::Sym(Sym::LBracket),
+         Token::Rec, Token::Sym(Sym::Dot), Token::Op(Operator::Equal),
+         Token::Fun, Token::Sym(Sym::Dot), Token::Sym(Sym::Arrow), rest @ ..] => {
+            let (body, rest) = expr(rest)?;
+            (Value::NamelessRecClosure(env, Box::new(body)), rest)
+        }
+        _ => return Err(anyhow::anyhow!("invalid closure"))
+    };
+    match rest {
+        [Token::Sym(Sym::RBracket), rest @ ..] => Ok((Expr::Value(value), rest)),
+        _ => Err(anyhow::anyhow!("']' not found"))
+    }
+}
+
+fn match_with(tokens: &[Token]) -> anyhow::Result<(Expr<'_>, &[Token])> {
+    match tokens {
+        [Token::Match, rest @ ..] => {
+            let (e0, rest) = expr(rest)?;
+            match rest {
+                [Token::With, rest @ ..] => {
+                    let (clauses, rest) = clauses(rest)?;
+                    Ok((Expr::Match(Box::new(e0), clauses), rest))
+                }
+                _ => Err(anyhow::anyhow!("with section not found"))
+            }
+        }
+        _ => Err(anyhow::anyhow!("internal: unexpected invoke match_with"))
+    }
+}
+
+// p -> e | p -> e | ...
+fn clauses(tokens: &[Token]) -> anyhow::Result<(Clauses<'_>, &[Token])> {
+    let (p, rest) = pattern(tokens)?;
+    let (e, rest) =
+        match rest {
+            [Token::Sym(Sym::Arrow), rest @ ..] => expr(rest)?,
+            _ => return Err(anyhow::anyhow!("'->' not found in match clause"))
+        };
+    let mut clauses = vec![(p, e)];
+    match rest {
+        [Token::Sym(Sym::Bar), rest @ ..] => {
+            let (mut others, rest) = self::clauses(rest)?;
+            clauses.append(&mut others);
+            Ok((clauses, rest))
+        }
+        _ => Ok((clauses, rest))
+    }
+}
+
+// EvalML5
+// p matches v when (ε) / p doesn't match v の p
+pub fn parse_pattern(tokens: &[Token]) -> anyhow::Result<Pattern<'_>> {
+    match pattern(tokens)? {
+        (p, []) => Ok(p),
+        _ => Err(anyhow::anyhow!("syntax error"))
+    }
+}
+
+// :: は右結合
+fn pattern(tokens: &[Token]) -> anyhow::Result<(Pattern<'_>, &[Token])> {
+    let (left, rest) = pattern_atom(tokens)?;
+    match rest {
+        [Token::Sym(Sym::Cons), rest @ ..] => {
+            let (right, rest) = pattern(rest)?;
+            Ok((Pattern::Cons(Box::new(left), Box::new(right)), rest))
+        }
+        _ => Ok((left, rest))
+    }
+}
+
+fn pattern_atom(tokens: &[Token]) -> anyhow::Result<(Pattern<'_>, &[Token])> {
+    match tokens {
+        [Token::Var(x), rest @ ..] if x == "_" => Ok((Pattern::Wild, rest)),
+        [Token::Var(x), rest @ ..] => Ok((Pattern::Var(x), rest)),
+        [Token::Sym(Sym::LBracket), Token::Sym(Sym::RBracket), rest @ ..] => Ok((Pattern::Nil, rest)),
+        [Token::Sym(Sym::LParen), rest @ ..] => {
+            let (p, rest) = pattern(rest)?;
+            match rest {
+                [Token::Sym(Sym::RParen), rest @ ..] => Ok((p, rest)),
+                _ => Err(anyhow::anyhow!("')' not found"))
+            }
         }
-        _ => unreachable!("parser: unreachable point at let on let_in")
+        _ => Err(anyhow::anyhow!("invalid pattern"))
     }
 }
@@ -0,0 +1,85 @@
+use std::fmt;
+
+use crate::derivation::Derivation;
+use crate::diff::{derive_reference, derive_claimed, key, subject};
+use crate::solver::System;
+
+// 判断が導出できるかを答える
+// 導出できなければ, 書かれた結果が solver の結果と食い違い始める一番小さい判断を示す
+pub enum Answer {
+    Derivable,
+    NotDerivable(String)
+}
+
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Answer::Derivable => write!(f, "derivable"),
+            Answer::NotDerivable(reason) => write!(f, "not derivable: {}", reason)
+        }
+    }
+}
+
+pub fn query(judgment: &str, sys: System) -> anyhow::Result<Answer> {
+    let typing = matches!(sys, System::TypingML4 | System::PolyTypingML4);
+    if let Ok(d) = derive_claimed(judgment, sys) {
+        if key(&d.judgment) == key(judgment) {
+            return Ok(Answer::Derivable)
+        }
+    }
+    let d = match derive_reference(judgment, sys) {
+        Ok(d) => d,
+        // 型が付かない式
+        Err(e) if typing => return Ok(Answer::NotDerivable(e.to_string())),
+        Err(e) => return Err(e)
+    };
+    if subject(&d.judgment) != subject(judgment) {
+        return Err(anyhow::anyhow!("cannot read `{}` as a judgment of {:?}", judgment, sys))
+    }
+    Ok(Answer::NotDerivable(explain(&d, &result(judgment))))
+}
+
+fn explain(d: &Derivation, claimed: &str) -> String {
+    let actual = result(&d.judgment);
+    // 結果をそのまま受け継いだ前提があれば, そちらで食い違っている
+    if let Some(premise) = d.premises.iter().rev().find(|p| result(&p.judgment) == actual) {
+        return explain(premise, claimed)
+    }
+    if actual == "error" {
+        return match operand(&d.rule, &d.premises) {
+            Some((what, premise)) =>
+                format!("{} evaluates to {}; {} applies, result is error", what, result(&premise.judgment), d.rule),
+            None => format!("{} applies, result is error", d.rule)
+        }
+    }
+    format!("{}, not {}", key(&d.judgment), claimed)
+}
+
+// エラーの規則で問題になった部分式とその前提
+fn operand<'d>(rule: &str, premises: &'d [Derivation]) -> Option<(&'static str, &'d Derivation)> {
+    let what = if rule.starts_with("E-If") {
+        "condition"
+    } else if rule.starts_with("E-App") {
+        "function"
+    } else if rule.ends_with('L') {
+        "left operand"
+    } else if rule.ends_with('R') {
+        "right operand"
+    } else {
+        "premise"
+    };
+    match what {
+        "right operand" | "premise" => premises.last().map(|p| (what, p)),
+        _ => premises.first().map(|p| (what, p))
+    }
+}
+
+// 判断の結果の部分 (evalto v の v など)
+fn result(judgment: &str) -> String {
+    let judgment = key(judgment);
+    let rest = judgment[subject(&judgment).len()..].trim();
+    match rest.split_once(' ') {
+        Some((_, result)) => result.to_string(),
+        None => rest.to_string()
+    }
+}
@@ -0,0 +1,199 @@
+use crate::expr::{Expr, Prim, Value, Env, EnvVar, Store, RefForm};
+use crate::derivation::Derivation;
+
+// BNF
+// EvalRefML3
+// e ∈ Exp ::= ... | ref e | !e | e := e
+// v ∈ Value ::= ... | @l
+// S ∈ Store ::= · | S, @l = v
+//
+// S1 / ε |- e evalto v / S2
+// ストアが空のときは S / を省略する
+
+pub fn solve(form: &RefForm) -> anyhow::Result<()> {
+    print!("{}", derive(form)?);
+    Ok(())
+}
+
+pub fn derive(form: &RefForm) -> anyhow::Result<Derivation> {
+    let RefForm(store, env, expr) = form;
+    eval(store, env, expr)?;
+    Ok(apply_rule(store, env, expr))
+}
+
+fn eval<'a>(store: &Store<'a>, env: &Env<'a>, expr: &Expr<'a>) -> anyhow::Result<(Value<'a>, Store<'a>)> {
+    match expr {
+        Expr::Value(v @ (Value::Int(_) | Value::Bool(_))) => Ok((v.clone(), store.clone())),
+        Expr::Prim(p) => {
+            let (l, r) = operands(p);
+            let (v1, s2) = eval(store, env, l)?;
+            let (v2, s3) = eval(&s2, env, r)?;
+            match (v1, v2) {
+                (Value::Int(l), Value::Int(r)) => Ok((prim_value(p, l, r), s3)),
+                (l, r) => Err(anyhow::anyhow!("expect int: {}, {}", l, r))
+            }
+        }
+        Expr::IfThenElse(cond, then, els) => {
+            match eval(store, env, cond)? {
+                (Value::Bool(true), s2) => eval(&s2, env, then),
+                (Value::Bool(false), s2) => eval(&s2, env, els),
+                (v, _) => Err(anyhow::anyhow!("expect bool: {}", v))
+            }
+        }
+        Expr::Ident(x) => {
+            let v = get_env_var(env, x).ok_or_else(|| anyhow::anyhow!("unbound variable: {}", x))?;
+            Ok((v, store.clone()))
+        }
+        Expr::Let(x, e1, e2) => {
+            let (v1, s2) = eval(store, env, e1)?;
+            eval(&s2, &bind(env, x, v1), e2)
+        }
+        Expr::Fun(x, body) => Ok((Value::Closure(env.clone(), x, body.clone()), store.clone())),
+        Expr::App(fun, arg) => {
+            let (closure, s2) = eval(store, env, fun)?;
+            let (v, s3) = eval(&s2, env, arg)?;
+            match app_env(&closure, v) {
+                Some((new_env, body)) => eval(&s3, &new_env, &body),
+                None => Err(anyhow::anyhow!("expect closure: {}", closure))
+            }
+        }
+        Expr::LetRec(fun, x, fun_exp, e) => eval(store, &rec_env(env, fun, x, fun_exp), e),
+        Expr::Ref(e) => {
+            let (v, s2) = eval(store, env, e)?;
+            let l = s2.fresh();
+            Ok((Value::Loc(l.clone()), s2.pushed(l, v)))
+        }
+        Expr::Deref(e) => {
+            match eval(store, env, e)? {
+                (Value::Loc(l), s2) => {
+                    let v = s2.get(&l).cloned().ok_or_else(|| anyhow::anyhow!("unknown location: @{}", l))?;
+                    Ok((v, s2))
+                }
+                (v, _) => Err(anyhow::anyhow!("expect location: {}", v))
+            }
+        }
+        Expr::Assign(e1, e2) => {
+            match eval(store, env, e1)? {
+                (Value::Loc(l), s2) => {
+                    let (v, s3) = eval(&s2, env, e2)?;
+                    if s3.get(&l).is_none() {
+                        return Err(anyhow::anyhow!("unknown location: @{}", l))
+                    }
+                    Ok((v.clone(), s3.updated(&l, v)))
+                }
+                (v, _) => Err(anyhow::anyhow!("expect location: {}", v))
+            }
+        }
+        _ => Err(anyhow::anyhow!("EvalRefML3 does not support: {}", expr))
+    }
+}
+
+fn operands<'a, 'b>(p: &'b Prim<'a>) -> (&'b Expr<'a>, &'b Expr<'a>) {
+    match p {
+        Prim::Add(l, r) | Prim::Sub(l, r) | Prim::Mul(l, r) | Prim::LessThan(l, r) => (l, r)
+    }
+}
+
+fn prim_value<'a>(p: &Prim<'a>, l: isize, r: isize) -> Value<'a> {
+    match p {
+        Prim::Add(_, _) => Value::Int(l + r),
+        Prim::Sub(_, _) => Value::Int(l - r),
+        Prim::Mul(_, _) => Value::Int(l * r),
+        Prim::LessThan(_, _) => Value::Bool(l < r)
+    }
+}
+
+fn bind<'a>(env: &Env<'a>, x: &'a String, v: Value<'a>) -> Env<'a> {
+    Env(vec![EnvVar(x, Box::new(Expr::Value(v)))]).appended(env)
+}
+
+// E-App: ε2, x = v2
+// E-AppRec: ε2, f = (ε2)[rec f = fun x -> e0], x = v2
+fn app_env<'a>(closure: &Value<'a>, arg: Value<'a>) -> Option<(Env<'a>, Expr<'a>)> {
+    match closure {
+        Value::Closure(env, x, body) => Some((bind(env, x, arg), *body.clone())),
+        Value::RecClosure(env, fun, x, body) => Some((bind(&bind(env, fun, closure.clone()), x, arg), *body.clone())),
+        _ => None
+    }
+}
+
+fn rec_env<'a>(env: &Env<'a>, fun: &'a String, x: &'a String, fun_exp: &Expr<'a>) -> Env<'a> {
+    bind(env, fun, Value::RecClosure(env.clone(), fun, x, Box::new(fun_exp.clone())))
+}
+
+fn get_env_var<'a>(env: &Env<'a>, name: &String) -> Option<Value<'a>> {
+    env.0.iter()
+        .find(|e| e.0 == name)
+        .and_then(|e| e.1.to_value())
+}
+
+// evalto v / S (ストアが空なら evalto v)
+fn result(v: &Value, store: &Store) -> String {
+    if store.0.is_empty() {
+        format!("{}", v)
+    } else {
+        format!("{} / {}", v, store)
+    }
+}
+
+// eval が成功した式に対してだけ呼ぶ
+fn apply_rule<'a>(store: &Store<'a>, env: &Env<'a>, expr: &Expr<'a>) -> Derivation {
+    let (evaled, s) = eval(store, env, expr).expect("internal: eval failed on apply_rule");
+    let judge = format!("{} {} evalto {}", store.form(env), expr, result(&evaled, &s));
+    match expr {
+        Expr::Value(Value::Bool(_)) => Derivation::new(judge, "E-Bool", vec![]),
+        Expr::Value(_) => Derivation::new(judge, "E-Int", vec![]),
+        Expr::Prim(p) => {
+            let (rule, op) = match p {
+                Prim::Add(_, _) => ("Plus", "plus"),
+                Prim::Sub(_, _) => ("Minus", "minus"),
+                Prim::Mul(_, _) => ("Times", "times"),
+                Prim::LessThan(_, _) => ("Lt", "less than")
+            };
+            let (l, r) = operands(p);
+            let (v1, s2) = eval(store, env, l).unwrap();
+            let (v2, _) = eval(&s2, env, r).unwrap();
+            let premises = vec![
+                apply_rule(store, env, l),
+                apply_rule(&s2, env, r),
+                Derivation::new(format!("{} {} {} is {}", v1, op, v2, evaled), &format!("B-{}", rule), vec![])
+            ];
+            Derivation::new(judge, &format!("E-{}", rule), premises)
+        }
+        Expr::IfThenElse(cond, then, els) => {
+            let (v, s2) = eval(store, env, cond).unwrap();
+            if let Value::Bool(true) = v {
+                Derivation::new(judge, "E-IfT", vec![apply_rule(store, env, cond), apply_rule(&s2, env, then)])
+            } else {
+                Derivation::new(judge, "E-IfF", vec![apply_rule(store, env, cond), apply_rule(&s2, env, els)])
+            }
+        }
+        Expr::Ident(_) => Derivation::new(judge, "E-Var", vec![]),
+        Expr::Let(x, e1, e2) => {
+            let (v1, s2) = eval(store, env, e1).unwrap();
+            Derivation::new(judge, "E-Let", vec![apply_rule(store, env, e1), apply_rule(&s2, &bind(env, x, v1), e2)])
+        }
+        Expr::Fun(_, _) => Derivation::new(judge, "E-Fun", vec![]),
+        Expr::App(fun, arg) => {
+            let (closure, s2) = eval(store, env, fun).unwrap();
+            let (v, s3) = eval(&s2, env, arg).unwrap();
+            let rule = match closure {
+                Value::RecClosure(_, _, _, _) => "E-AppRec",
+                _ => "E-App"
+            };
+            let (new_env, body) = app_env(&closure, v).unwrap();
+            let premises = vec![apply_rule(store, env, fun), apply_rule(&s2, env, arg), apply_rule(&s3, &new_env, &body)];
+            Derivation::new(judge, rule, premises)
+        }
+        Expr::LetRec(fun, x, fun_exp, e) => {
+            Derivation::new(judge, "E-LetRec", vec![apply_rule(store, &rec_env(env, fun, x, fun_exp), e)])
+        }
+        Expr::Ref(e) => Derivation::new(judge, "E-Ref", vec![apply_rule(store, env, e)]),
+        Expr::Deref(e) => Derivation::new(judge, "E-Deref", vec![apply_rule(store, env, e)]),
+        Expr::Assign(e1, e2) => {
+            let (_, s2) = eval(store, env, e1).unwrap();
+            Derivation::new(judge, "E-Assign", vec![apply_rule(store, env, e1), apply_rule(&s2, env, e2)])
+        }
+        _ => unreachable!("internal: unsupported expression on apply_rule")
+    }
+}
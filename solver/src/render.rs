@@ -0,0 +1,98 @@
+use std::str::FromStr;
+
+use crate::bar::{bar, Page};
+use crate::derivation::Derivation;
+use crate::dot::{dot, Graph};
+use crate::html::html;
+use crate::latex::latex;
+use crate::schema::export;
+use crate::solver::System;
+
+// 導出の書き出し方
+pub enum Format {
+    // solver の出力の形 (字下げなし)
+    Flat,
+    // exercise/ の答えと同じ CoPL の形
+    Copl(Layout),
+    // schema.rs の JSON
+    Json,
+    // bussproofs の LaTeX
+    Latex,
+    // Graphviz の DOT
+    Dot(Graph),
+    // 畳める導出の HTML
+    Html,
+    // 端末で読む教科書の形 (前提, 横線, 結論)
+    Tree(Page)
+}
+
+pub struct Layout {
+    // 深さ一つあたりの空白の数
+    pub indent: usize,
+    // 一番外の導出の後ろにも ; を付ける (exercise の答えの形, 採点システムは付けない形も受け付ける)
+    pub semicolon: bool
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout { indent: 2, semicolon: true }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat" => Ok(Format::Flat),
+            "copl" => Ok(Format::Copl(Layout::default())),
+            "json" => Ok(Format::Json),
+            "latex" => Ok(Format::Latex),
+            "dot" => Ok(Format::Dot(Graph::default())),
+            "html" => Ok(Format::Html),
+            "tree" => Ok(Format::Tree(Page::default())),
+            _ => Err(anyhow::anyhow!("unknown format: {}; expected flat, copl, json, latex, dot, html or tree", s))
+        }
+    }
+}
+
+pub fn render(d: &Derivation, sys: System, format: &Format) -> String {
+    match format {
+        Format::Flat => d.to_string(),
+        Format::Json => format!("{}\n", export(d, sys)),
+        Format::Latex => latex(d),
+        Format::Dot(graph) => dot(d, sys, graph),
+        Format::Html => html(d),
+        Format::Tree(page) => bar(d, page),
+        Format::Copl(layout) => {
+            let mut out = String::new();
+            copl(&mut out, d, layout, 0);
+            out.push_str(if layout.semicolon { ";\n" } else { "\n" });
+            out
+        }
+    }
+}
+
+// judgment by RULE {
+//   premise;
+// }
+// 最後の ; と改行は呼び出し側で付ける
+fn copl(out: &mut String, d: &Derivation, layout: &Layout, depth: usize) {
+    let indent = " ".repeat(layout.indent * depth);
+    // solver の判断は空の環境で空白から始まる
+    out.push_str(&format!("{}{} by {} ", indent, d.judgment.trim(), d.rule));
+    if d.hole {
+        out.push('?');
+        return
+    }
+    if d.premises.is_empty() {
+        out.push_str("{}");
+        return
+    }
+    out.push_str("{\n");
+    for premise in &d.premises {
+        copl(out, premise, layout, depth + 1);
+        out.push_str(";\n");
+    }
+    out.push_str(&format!("{}}}", indent));
+}
@@ -0,0 +1,541 @@
+use crate::derivation::Derivation;
+use crate::diff::key;
+use crate::expr::{Expr, Prim, Value, Env, EnvVar, Pattern, NamelessEnv, Cont, ContForm, Frame, Store};
+use crate::json::Json;
+use crate::nat::{NatExp, NatJudgment, parse_nat_judgment};
+use crate::parser::{parse, parse_value, parse_values, parse_vars, parse_typing, parse_cont, parse_cont_env, parse_store, parse_ref_result, parse_pattern};
+use crate::solver::System;
+use crate::tokenizer::tokenize;
+use crate::types::{Type, Scheme, TypeEnv, var_index};
+
+// 導出の JSON での形 (version 1)
+//
+// { "version": 1, "system": "EvalML3", "derivation": node }
+// node = { "judgment": "x = 3 |- x + 1 evalto 4", "form": { ... }, "rule": "E-Plus", "premises": [node, ...] }
+//
+// judgment は判断をそのまま書いた文字列で, 読み込むときはこれを使う
+// form は判断を読んだ結果で, "kind" で種類を分ける (読めない判断には付けない)
+//   eval:          { env, expr, value }                          EvalML1..EvalML5
+//   matches:       { pattern, value, env }                       EvalML5 (p matches v when (ε))
+//   no-match:      { pattern, value }                            EvalML5 (p doesn't match v)
+//   nameless-eval: { env: [value], expr, value }                 EvalNamelessML3
+//   translate:     { vars: [name], expr, result }                NamelessML3
+//   cont-eval:     { env?, expr, cont: [frame], value }          EvalContML1, EvalContML4 (env は EvalContML4 だけ)
+//   cont-ret:      { arg, cont: [frame], value }                 EvalContML1, EvalContML4 (v => k evalto v)
+//   ref-eval:      { store: [{ name, value }], env, expr, value, final-store }    EvalRefML3
+//   arith:         { op: plus | minus | times | less than, left, right, result }
+//   typing:        { env: [{ name, scheme }], expr, type }       TypingML4, PolyTypingML4
+//   plus | times | less | evalto | reduce | det-reduce | multi-reduce    Nat, CompareNat, EvalNatExp, ReduceNatExp
+// 評価の結果が error なら value は { "kind": "error" }
+// 前提が ? で省略されていれば premises は null
+//
+// 式, 値, 型も "kind" で種類を分けたオブジェクトにする (expr_json などを参照)
+// 環境とストアは書いた順 (左が古い) の配列に, 継続は先に使うフレームを先頭にした配列にする
+// frame = { env?, expr } (env は環境を持つ EvalContML4 のフレームだけ)
+pub const VERSION: i64 = 1;
+
+pub fn export(d: &Derivation, sys: System) -> Json {
+    Json::object(vec![
+        ("version", Json::Int(VERSION)),
+        ("system", Json::str(&format!("{:?}", sys))),
+        ("derivation", node_json(d, sys))
+    ])
+}
+
+fn node_json(d: &Derivation, sys: System) -> Json {
+    let mut fields = vec![("judgment", Json::str(d.judgment.trim()))];
+    if let Some(form) = form_json(&d.judgment, sys) {
+        fields.push(("form", form));
+    }
+    fields.push(("rule", Json::str(&d.rule)));
+    fields.push(("premises", match d.hole {
+        true => Json::Null,
+        false => Json::Array(d.premises.iter().map(|p| node_json(p, sys)).collect())
+    }));
+    Json::object(fields)
+}
+
+// 読み込んだ導出には行番号がない
+// form があれば judgment と食い違っていないかを確かめる
+pub fn import(json: &Json) -> anyhow::Result<(Derivation, System)> {
+    let version = json.field("version")?.as_int()?;
+    if version != VERSION {
+        return Err(anyhow::anyhow!("json: unsupported version {}; expected {}", version, VERSION))
+    }
+    let sys = json.field("system")?.as_str()?.parse::<System>()?;
+    let d = import_node(json.field("derivation")?, sys, &mut vec![])?;
+    Ok((d, sys))
+}
+
+fn import_node(json: &Json, sys: System, path: &mut Vec<usize>) -> anyhow::Result<Derivation> {
+    let at = |path: &[usize]| match path {
+        [] => "root".to_string(),
+        _ => format!("root.{}", path.iter().map(|i| i.to_string()).collect::<Vec<_>>().join("."))
+    };
+    let judgment = json.field("judgment")?.as_str()?.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some(form) = json.get("form") {
+        if form_json(&judgment, sys).as_ref() != Some(form) {
+            return Err(anyhow::anyhow!("json: {}: form does not match judgment `{}`", at(path), judgment))
+        }
+    }
+    let rule = json.field("rule")?.as_str()?;
+    let (premises, hole) = match json.field("premises")? {
+        Json::Null => (vec![], true),
+        premises => {
+            let mut ds = vec![];
+            for (i, p) in premises.as_array()?.iter().enumerate() {
+                path.push(i + 1);
+                ds.push(import_node(p, sys, path)?);
+                path.pop();
+            }
+            (ds, false)
+        }
+    };
+    Ok(Derivation { judgment, rule: rule.to_string(), premises, line: 0, hole })
+}
+
+// 判断を体系に合わせて読む
+fn form_json(judgment: &str, sys: System) -> Option<Json> {
+    let judgment = key(judgment);
+    match sys {
+        System::EvalML1 | System::EvalML3 | System::EvalML4 => eval_form(&judgment).or_else(|| arith_form(&judgment)),
+        System::EvalML5 => eval_form(&judgment).or_else(|| match_form(&judgment)).or_else(|| arith_form(&judgment)),
+        System::EvalNamelessML3 => nameless_eval_form(&judgment).or_else(|| arith_form(&judgment)),
+        System::NamelessML3 => translate_form(&judgment),
+        System::TypingML4 | System::PolyTypingML4 => typing_form(&judgment),
+        System::Nat | System::CompareNat1 | System::CompareNat2 | System::CompareNat3
+            | System::EvalNatExp | System::ReduceNatExp => parse_nat_judgment(&judgment).ok().map(|j| nat_judgment_json(&j)),
+        System::EvalContML1 => cont_form(&judgment, false).or_else(|| arith_form(&judgment)),
+        System::EvalContML4 => cont_form(&judgment, true).or_else(|| arith_form(&judgment)),
+        System::EvalRefML3 => ref_form(&judgment).or_else(|| arith_form(&judgment))
+    }
+}
+
+fn eval_form(judgment: &str) -> Option<Json> {
+    let (form, value) = judgment.rsplit_once(" evalto ")?;
+    let (env, expr) = form.split_once("|-").unwrap_or(("", form));
+    let (env, expr) = (tokenize(env.as_bytes()).ok()?, tokenize(expr.as_bytes()).ok()?);
+    let form = parse(&env, &expr).ok()?;
+    Some(Json::object(vec![
+        ("kind", Json::str("eval")),
+        ("env", env_json(&form.0)),
+        ("expr", expr_json(&form.1)),
+        ("value", result_json(value)?)
+    ]))
+}
+
+// p matches v when (ε) / p doesn't match v
+fn match_form(judgment: &str) -> Option<Json> {
+    if let Some((p, v)) = judgment.split_once(" doesn't match ") {
+        let (p, v) = (tokenize(p.as_bytes()).ok()?, tokenize(v.as_bytes()).ok()?);
+        return Some(Json::object(vec![
+            ("kind", Json::str("no-match")),
+            ("pattern", pattern_json(&parse_pattern(&p).ok()?)),
+            ("value", value_json(&parse_value(&v).ok()?))
+        ]))
+    }
+    let (p, rest) = judgment.split_once(" matches ")?;
+    let (v, env) = rest.rsplit_once(" when ")?;
+    let env = env.strip_prefix('(')?.strip_suffix(')')?;
+    let (p, v, env) = (tokenize(p.as_bytes()).ok()?, tokenize(v.as_bytes()).ok()?, tokenize(env.as_bytes()).ok()?);
+    // 値を式として読めば環境と一緒に読める
+    let form = parse(&env, &v).ok()?;
+    Some(Json::object(vec![
+        ("kind", Json::str("matches")),
+        ("pattern", pattern_json(&parse_pattern(&p).ok()?)),
+        ("value", value_json(&form.1.to_value()?)),
+        ("env", env_json(&form.0))
+    ]))
+}
+
+fn nameless_eval_form(judgment: &str) -> Option<Json> {
+    let (form, value) = judgment.rsplit_once(" evalto ")?;
+    let (env, expr) = form.split_once("|-").unwrap_or(("", form));
+    let (env, expr) = (tokenize(env.as_bytes()).ok()?, tokenize(expr.as_bytes()).ok()?);
+    let form = parse_values(&env, &expr).ok()?;
+    Some(Json::object(vec![
+        ("kind", Json::str("nameless-eval")),
+        ("env", nameless_env_json(&form.0)),
+        ("expr", expr_json(&form.1)),
+        ("value", result_json(value)?)
+    ]))
+}
+
+fn translate_form(judgment: &str) -> Option<Json> {
+    let (form, result) = judgment.rsplit_once(" ==> ")?;
+    let (vars, expr) = form.split_once("|-").unwrap_or(("", form));
+    let (vars, expr, result) = (tokenize(vars.as_bytes()).ok()?, tokenize(expr.as_bytes()).ok()?, tokenize(result.as_bytes()).ok()?);
+    let form = parse_vars(&vars, &expr).ok()?;
+    let result = parse_values(&[], &result).ok()?;
+    Some(Json::object(vec![
+        ("kind", Json::str("translate")),
+        ("vars", Json::Array(form.0.0.iter().rev().map(|x| Json::str(x)).collect())),
+        ("expr", expr_json(&form.1)),
+        ("result", expr_json(&result.1))
+    ]))
+}
+
+// ε |- e >> k evalto v / v => k evalto v
+// 継続のフレームにも |- が現れるので, まず環境のない形として読む
+fn cont_form(judgment: &str, with_env: bool) -> Option<Json> {
+    let (form, value) = judgment.rsplit_once(" evalto ")?;
+    let tokens = tokenize(form.as_bytes()).ok()?;
+    let (env, expr) = form.split_once("|-").unwrap_or(("", form));
+    let (env, expr) = (tokenize(env.as_bytes()).ok()?, tokenize(expr.as_bytes()).ok()?);
+    let form = match parse_cont(&tokens) {
+        Ok(form) => form,
+        Err(_) if with_env => parse_cont_env(&env, &expr).ok()?,
+        Err(_) => return None
+    };
+    Some(match form {
+        ContForm::Eval(env, e, k) => {
+            let mut fields = vec![("kind", Json::str("cont-eval"))];
+            if with_env {
+                fields.push(("env", env_json(&env.unwrap_or(Env(vec![])))));
+            }
+            fields.extend([("expr", expr_json(&e)), ("cont", cont_json(&k)), ("value", result_json(value)?)]);
+            Json::object(fields)
+        }
+        ContForm::Ret(v, k) => Json::object(vec![
+            ("kind", Json::str("cont-ret")),
+            ("arg", value_json(&v)),
+            ("cont", cont_json(&k)),
+            ("value", result_json(value)?)
+        ])
+    })
+}
+
+// S / ε |- e evalto v / S'
+fn ref_form(judgment: &str) -> Option<Json> {
+    let (form, result) = judgment.rsplit_once(" evalto ")?;
+    let (env, expr) = form.split_once("|-").unwrap_or(("", form));
+    let (env, expr, result) = (tokenize(env.as_bytes()).ok()?, tokenize(expr.as_bytes()).ok()?, tokenize(result.as_bytes()).ok()?);
+    let form = parse_store(&env, &expr).ok()?;
+    let (value, after) = parse_ref_result(&result).ok()?;
+    Some(Json::object(vec![
+        ("kind", Json::str("ref-eval")),
+        ("store", store_json(&form.0)),
+        ("env", env_json(&form.1)),
+        ("expr", expr_json(&form.2)),
+        ("value", value_json(&value)),
+        ("final-store", store_json(&after))
+    ]))
+}
+
+// 評価の結果 (値か error)
+fn result_json(value: &str) -> Option<Json> {
+    if value.trim() == "error" {
+        return Some(Json::object(vec![("kind", Json::str("error"))]))
+    }
+    let tokens = tokenize(value.as_bytes()).ok()?;
+    parse_value(&tokens).ok().map(|v| value_json(&v))
+}
+
+// i1 plus i2 is i3 など
+fn arith_form(judgment: &str) -> Option<Json> {
+    let (left_op_right, result) = judgment.rsplit_once(" is ")?;
+    let (left, op, right) = ["plus", "minus", "times", "less than"].iter()
+        .find_map(|op| left_op_right.split_once(&format!(" {} ", op)).map(|(l, r)| (l, *op, r)))?;
+    let result = match op {
+        "less than" => Json::Bool(result.parse().ok()?),
+        _ => Json::Int(result.parse().ok()?)
+    };
+    Some(Json::object(vec![
+        ("kind", Json::str("arith")),
+        ("op", Json::str(op)),
+        ("left", Json::Int(left.parse().ok()?)),
+        ("right", Json::Int(right.parse().ok()?)),
+        ("result", result)
+    ]))
+}
+
+fn typing_form(judgment: &str) -> Option<Json> {
+    let (env, expr) = judgment.split_once("|-").unwrap_or(("", judgment));
+    let (env, expr) = (tokenize(env.as_bytes()).ok()?, tokenize(expr.as_bytes()).ok()?);
+    let form = parse_typing(&env, &expr).ok()?;
+    Some(Json::object(vec![
+        ("kind", Json::str("typing")),
+        ("env", type_env_json(&form.0)),
+        ("expr", expr_json(&form.1)),
+        ("type", type_json(form.2.as_ref()?))
+    ]))
+}
+
+fn nat_judgment_json(j: &NatJudgment) -> Json {
+    let nat = |n: &usize| Json::Int(*n as i64);
+    let (kind, fields) = match j {
+        NatJudgment::Plus(n1, n2, n3) => ("plus", vec![("left", nat(n1)), ("right", nat(n2)), ("result", nat(n3))]),
+        NatJudgment::Times(n1, n2, n3) => ("times", vec![("left", nat(n1)), ("right", nat(n2)), ("result", nat(n3))]),
+        NatJudgment::Less(n1, n2) => ("less", vec![("left", nat(n1)), ("right", nat(n2))]),
+        NatJudgment::EvalTo(e, n) => ("evalto", vec![("expr", nat_exp_json(e)), ("result", nat(n))]),
+        NatJudgment::Reduce(e1, e2) => ("reduce", vec![("from", nat_exp_json(e1)), ("to", nat_exp_json(e2))]),
+        NatJudgment::DetReduce(e1, e2) => ("det-reduce", vec![("from", nat_exp_json(e1)), ("to", nat_exp_json(e2))]),
+        NatJudgment::MultiReduce(e1, e2) => ("multi-reduce", vec![("from", nat_exp_json(e1)), ("to", nat_exp_json(e2))])
+    };
+    node(kind, fields)
+}
+
+// ペアノ数は S の数で書く
+fn nat_exp_json(e: &NatExp) -> Json {
+    match e {
+        NatExp::Nat(n) => Json::object(vec![("kind", Json::str("nat")), ("value", Json::Int(*n as i64))]),
+        NatExp::Plus(l, r) => Json::object(vec![("kind", Json::str("plus")), ("left", nat_exp_json(l)), ("right", nat_exp_json(r))]),
+        NatExp::Times(l, r) => Json::object(vec![("kind", Json::str("times")), ("left", nat_exp_json(l)), ("right", nat_exp_json(r))])
+    }
+}
+
+fn node(kind: &str, fields: Vec<(&str, Json)>) -> Json {
+    let mut all = vec![("kind", Json::str(kind))];
+    all.extend(fields);
+    Json::object(all)
+}
+
+pub fn expr_json(e: &Expr) -> Json {
+    match e {
+        Expr::Value(v) => value_json(v),
+        Expr::Prim(p) => {
+            let (op, l, r) = match p {
+                Prim::Add(l, r) => ("plus", l, r),
+                Prim::Sub(l, r) => ("minus", l, r),
+                Prim::Mul(l, r) => ("times", l, r),
+                Prim::LessThan(l, r) => ("lt", l, r)
+            };
+            node(op, vec![("left", expr_json(l)), ("right", expr_json(r))])
+        }
+        Expr::IfThenElse(c, t, f) => node("if", vec![("cond", expr_json(c)), ("then", expr_json(t)), ("else", expr_json(f))]),
+        Expr::Ident(x) => node("var", vec![("name", Json::str(x))]),
+        Expr::Let(x, e1, e2) => node("let", vec![("name", Json::str(x)), ("bound", expr_json(e1)), ("body", expr_json(e2))]),
+        Expr::Fun(x, e) => node("fun", vec![("param", Json::str(x)), ("body", expr_json(e))]),
+        Expr::App(e1, e2) => node("app", vec![("fun", expr_json(e1)), ("arg", expr_json(e2))]),
+        Expr::LetRec(f, x, e1, e2) =>
+            node("letrec", vec![("name", Json::str(f)), ("param", Json::str(x)), ("fun", expr_json(e1)), ("body", expr_json(e2))]),
+        Expr::Cons(l, r) => node("cons", vec![("head", expr_json(l)), ("tail", expr_json(r))]),
+        Expr::Match(e, clauses) => node("match", vec![
+            ("expr", expr_json(e)),
+            ("clauses", Json::Array(clauses.iter()
+                .map(|(p, e)| Json::object(vec![("pattern", pattern_json(p)), ("body", expr_json(e))]))
+                .collect()))
+        ]),
+        Expr::Index(i) => node("index", vec![("index", Json::Int(*i as i64))]),
+        Expr::NamelessLet(e1, e2) => node("nameless-let", vec![("bound", expr_json(e1)), ("body", expr_json(e2))]),
+        Expr::NamelessFun(e) => node("nameless-fun", vec![("body", expr_json(e))]),
+        Expr::NamelessLetRec(e1, e2) => node("nameless-letrec", vec![("fun", expr_json(e1)), ("body", expr_json(e2))]),
+        Expr::Hole => node("hole", vec![]),
+        Expr::LetCc(k, e) => node("letcc", vec![("name", Json::str(k)), ("body", expr_json(e))]),
+        Expr::Ref(e) => node("ref", vec![("expr", expr_json(e))]),
+        Expr::Deref(e) => node("deref", vec![("expr", expr_json(e))]),
+        Expr::Assign(e1, e2) => node("assign", vec![("target", expr_json(e1)), ("value", expr_json(e2))])
+    }
+}
+
+fn pattern_json(p: &Pattern) -> Json {
+    match p {
+        Pattern::Var(x) => node("var", vec![("name", Json::str(x))]),
+        Pattern::Nil => node("nil", vec![]),
+        Pattern::Cons(l, r) => node("cons", vec![("head", pattern_json(l)), ("tail", pattern_json(r))]),
+        Pattern::Wild => node("wild", vec![])
+    }
+}
+
+pub fn value_json(v: &Value) -> Json {
+    match v {
+        Value::Int(i) => node("int", vec![("value", Json::Int(*i as i64))]),
+        Value::Bool(b) => node("bool", vec![("value", Json::Bool(*b))]),
+        Value::Closure(env, x, e) => node("closure", vec![("env", env_json(env)), ("param", Json::str(x)), ("body", expr_json(e))]),
+        Value::RecClosure(env, f, x, e) =>
+            node("rec-closure", vec![("env", env_json(env)), ("name", Json::str(f)), ("param", Json::str(x)), ("body", expr_json(e))]),
+        Value::Nil => node("nil", vec![]),
+        Value::Cons(l, r) => node("cons", vec![("head", value_json(l)), ("tail", value_json(r))]),
+        Value::NamelessClosure(env, e) => node("nameless-closure", vec![("env", nameless_env_json(env)), ("body", expr_json(e))]),
+        Value::NamelessRecClosure(env, e) => node("nameless-rec-closure", vec![("env", nameless_env_json(env)), ("body", expr_json(e))]),
+        Value::Cont(k) => node("cont", vec![("frames", cont_json(k))]),
+        Value::Loc(l) => node("loc", vec![("name", Json::str(l))])
+    }
+}
+
+// 環境の変数には値が入っている
+pub fn env_json(env: &Env) -> Json {
+    Json::Array(env.0.iter().rev().map(|EnvVar(x, e)| {
+        let value = match e.as_ref() {
+            Expr::Value(v) => value_json(v),
+            e => expr_json(e)
+        };
+        Json::object(vec![("name", Json::str(x)), ("value", value)])
+    }).collect())
+}
+
+fn store_json(store: &Store) -> Json {
+    Json::Array(store.0.iter().map(|(l, v)| Json::object(vec![("name", Json::str(l)), ("value", value_json(v))])).collect())
+}
+
+// 先に使うフレームが先頭
+fn cont_json(k: &Cont) -> Json {
+    Json::Array(k.0.iter().map(|Frame(env, e)| {
+        let mut fields = vec![];
+        if let Some(env) = env {
+            fields.push(("env", env_json(env)));
+        }
+        fields.push(("expr", expr_json(e)));
+        Json::object(fields)
+    }).collect())
+}
+
+fn nameless_env_json(env: &NamelessEnv) -> Json {
+    Json::Array(env.0.iter().rev().map(value_json).collect())
+}
+
+fn type_env_json(env: &TypeEnv) -> Json {
+    Json::Array(env.0.iter().rev().map(|(x, s)| Json::object(vec![("name", Json::str(x)), ("scheme", scheme_json(s))])).collect())
+}
+
+fn scheme_json(s: &Scheme) -> Json {
+    Json::object(vec![
+        ("vars", Json::Array(s.0.iter().map(|n| Json::str(&type_var_name(*n))).collect())),
+        ("type", type_json(&s.1))
+    ])
+}
+
+pub fn type_json(t: &Type) -> Json {
+    match t {
+        Type::Int => node("int", vec![]),
+        Type::Bool => node("bool", vec![]),
+        Type::Fun(t1, t2) => node("fun", vec![("param", type_json(t1)), ("result", type_json(t2))]),
+        Type::List(t) => node("list", vec![("elem", type_json(t))]),
+        Type::Var(n) => node("var", vec![("name", Json::str(&type_var_name(*n)))])
+    }
+}
+
+// 'a の a
+fn type_var_name(n: usize) -> String {
+    Type::Var(n).to_string().trim_start_matches('\'').to_string()
+}
+
+// JSON から式や値を組み立てる
+// 式は変数名を借りるので, 先に names で名前を集めておき, それを借りる
+//
+// let mut ns = vec![];
+// names(&json, &mut ns);
+// let e = expr_from_json(&json, &ns)?;
+pub fn names(json: &Json, out: &mut Vec<String>) {
+    match json {
+        Json::Str(s) if !out.contains(s) => out.push(s.clone()),
+        Json::Array(items) => items.iter().for_each(|j| names(j, out)),
+        Json::Object(fields) => fields.iter().for_each(|(_, j)| names(j, out)),
+        _ => ()
+    }
+}
+
+fn name<'a>(json: &Json, names: &'a [String]) -> anyhow::Result<&'a String> {
+    let s = json.as_str()?;
+    names.iter().find(|n| *n == s).ok_or_else(|| anyhow::anyhow!("json: name {} was not collected", s))
+}
+
+fn kind(json: &Json) -> anyhow::Result<&str> {
+    json.field("kind")?.as_str()
+}
+
+pub fn expr_from_json<'a>(json: &Json, names: &'a [String]) -> anyhow::Result<Expr<'a>> {
+    let sub = |key: &str| -> anyhow::Result<Box<Expr<'a>>> { Ok(Box::new(expr_from_json(json.field(key)?, names)?)) };
+    let name = |key: &str| name(json.field(key)?, names);
+    Ok(match kind(json)? {
+        "plus" => Expr::Prim(Prim::Add(sub("left")?, sub("right")?)),
+        "minus" => Expr::Prim(Prim::Sub(sub("left")?, sub("right")?)),
+        "times" => Expr::Prim(Prim::Mul(sub("left")?, sub("right")?)),
+        "lt" => Expr::Prim(Prim::LessThan(sub("left")?, sub("right")?)),
+        "if" => Expr::IfThenElse(sub("cond")?, sub("then")?, sub("else")?),
+        "var" => Expr::Ident(name("name")?),
+        "let" => Expr::Let(name("name")?, sub("bound")?, sub("body")?),
+        "fun" => Expr::Fun(name("param")?, sub("body")?),
+        "app" => Expr::App(sub("fun")?, sub("arg")?),
+        "letrec" => Expr::LetRec(name("name")?, name("param")?, sub("fun")?, sub("body")?),
+        "cons" => Expr::Cons(sub("head")?, sub("tail")?),
+        "match" => {
+            let mut clauses = vec![];
+            for c in json.field("clauses")?.as_array()? {
+                clauses.push((pattern_from_json(c.field("pattern")?, names)?, expr_from_json(c.field("body")?, names)?));
+            }
+            Expr::Match(sub("expr")?, clauses)
+        }
+        "index" => Expr::Index(json.field("index")?.as_int()? as usize),
+        "nameless-let" => Expr::NamelessLet(sub("bound")?, sub("body")?),
+        "nameless-fun" => Expr::NamelessFun(sub("body")?),
+        "nameless-letrec" => Expr::NamelessLetRec(sub("fun")?, sub("body")?),
+        "hole" => Expr::Hole,
+        "letcc" => Expr::LetCc(name("name")?, sub("body")?),
+        "ref" => Expr::Ref(sub("expr")?),
+        "deref" => Expr::Deref(sub("expr")?),
+        "assign" => Expr::Assign(sub("target")?, sub("value")?),
+        _ => Expr::Value(value_from_json(json, names)?)
+    })
+}
+
+fn pattern_from_json<'a>(json: &Json, names: &'a [String]) -> anyhow::Result<Pattern<'a>> {
+    Ok(match kind(json)? {
+        "var" => Pattern::Var(name(json.field("name")?, names)?),
+        "nil" => Pattern::Nil,
+        "cons" => Pattern::Cons(Box::new(pattern_from_json(json.field("head")?, names)?), Box::new(pattern_from_json(json.field("tail")?, names)?)),
+        "wild" => Pattern::Wild,
+        k => return Err(anyhow::anyhow!("json: unknown pattern kind {}", k))
+    })
+}
+
+pub fn value_from_json<'a>(json: &Json, names: &'a [String]) -> anyhow::Result<Value<'a>> {
+    let body = || -> anyhow::Result<Box<Expr<'a>>> { Ok(Box::new(expr_from_json(json.field("body")?, names)?)) };
+    let name = |key: &str| name(json.field(key)?, names);
+    Ok(match kind(json)? {
+        "int" => Value::Int(json.field("value")?.as_int()? as isize),
+        "bool" => Value::Bool(json.field("value")?.as_bool()?),
+        "closure" => Value::Closure(env_from_json(json.field("env")?, names)?, name("param")?, body()?),
+        "rec-closure" => Value::RecClosure(env_from_json(json.field("env")?, names)?, name("name")?, name("param")?, body()?),
+        "nil" => Value::Nil,
+        "cons" => Value::Cons(Box::new(value_from_json(json.field("head")?, names)?), Box::new(value_from_json(json.field("tail")?, names)?)),
+        "nameless-closure" => Value::NamelessClosure(nameless_env_from_json(json.field("env")?, names)?, body()?),
+        "nameless-rec-closure" => Value::NamelessRecClosure(nameless_env_from_json(json.field("env")?, names)?, body()?),
+        "loc" => Value::Loc(json.field("name")?.as_str()?.to_string()),
+        "cont" => Value::Cont(cont_from_json(json.field("frames")?, names)?),
+        k => return Err(anyhow::anyhow!("json: unknown kind {}", k))
+    })
+}
+
+pub fn env_from_json<'a>(json: &Json, names: &'a [String]) -> anyhow::Result<Env<'a>> {
+    let mut vars = vec![];
+    for var in json.as_array()?.iter().rev() {
+        let value = value_from_json(var.field("value")?, names)?;
+        vars.push(EnvVar(name(var.field("name")?, names)?, Box::new(Expr::Value(value))));
+    }
+    Ok(Env(vars))
+}
+
+fn cont_from_json<'a>(json: &Json, names: &'a [String]) -> anyhow::Result<Cont<'a>> {
+    let mut frames = vec![];
+    for frame in json.as_array()? {
+        let env = match frame.get("env") {
+            Some(env) => Some(env_from_json(env, names)?),
+            None => None
+        };
+        frames.push(Frame(env, expr_from_json(frame.field("expr")?, names)?));
+    }
+    Ok(Cont(frames))
+}
+
+fn nameless_env_from_json<'a>(json: &Json, names: &'a [String]) -> anyhow::Result<NamelessEnv<'a>> {
+    let mut values = vec![];
+    for v in json.as_array()?.iter().rev() {
+        values.push(value_from_json(v, names)?);
+    }
+    Ok(NamelessEnv(values))
+}
+
+pub fn type_from_json(json: &Json) -> anyhow::Result<Type> {
+    Ok(match kind(json)? {
+        "int" => Type::Int,
+        "bool" => Type::Bool,
+        "fun" => Type::Fun(Box::new(type_from_json(json.field("param")?)?), Box::new(type_from_json(json.field("result")?)?)),
+        "list" => Type::List(Box::new(type_from_json(json.field("elem")?)?)),
+        "var" => {
+            let name = json.field("name")?.as_str()?;
+            Type::Var(var_index(name).ok_or_else(|| anyhow::anyhow!("json: bad type variable {}", name))?)
+        }
+        k => return Err(anyhow::anyhow!("json: unknown type kind {}", k))
+    })
+}
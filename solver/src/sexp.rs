@@ -0,0 +1,168 @@
+use std::fmt;
+
+use crate::expr::{Cont, Env, EnvVar, Expr, Frame, NamelessEnv, Pattern, Prim, Store, Value, Vars};
+use crate::types::{Scheme, Type, TypeEnv};
+
+// --dump-ast, --dump-env で書く S 式
+// 節の名前は schema.rs の JSON の kind に揃える
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>)
+}
+
+impl Sexp {
+    fn atom(s: &str) -> Sexp {
+        Sexp::Atom(s.to_string())
+    }
+
+    fn node(head: &str, mut items: Vec<Sexp>) -> Sexp {
+        items.insert(0, Sexp::atom(head));
+        Sexp::List(items)
+    }
+
+    fn width(&self) -> usize {
+        match self {
+            Sexp::Atom(s) => s.chars().count(),
+            Sexp::List(items) => items.iter().map(|i| i.width() + 1).sum::<usize>() + 1
+        }
+    }
+}
+
+// 一行に収まらないリストは, 頭とそれに続く名前 (アトム) の後ろの要素を一つずつ二つ字下げして書く
+impl fmt::Display for Sexp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_sexp(f, self, 0)
+    }
+}
+
+const WIDTH: usize = 60;
+
+fn write_sexp(f: &mut fmt::Formatter, sexp: &Sexp, depth: usize) -> fmt::Result {
+    match sexp {
+        Sexp::Atom(s) => write!(f, "{}", s),
+        Sexp::List(items) if items.is_empty() => write!(f, "()"),
+        Sexp::List(items) => {
+            let inline = sexp.width() + 2 * depth <= WIDTH;
+            // (letrec g n ... のように頭に続くアトムは頭と同じ行に置く
+            let names = items.iter().take_while(|i| matches!(i, Sexp::Atom(_))).count().max(1);
+            write!(f, "(")?;
+            write_sexp(f, &items[0], depth + 1)?;
+            for (i, item) in items.iter().enumerate().skip(1) {
+                if inline || i < names {
+                    write!(f, " ")?;
+                } else {
+                    write!(f, "\n{}", "  ".repeat(depth + 1))?;
+                }
+                write_sexp(f, item, depth + 1)?;
+            }
+            write!(f, ")")
+        }
+    }
+}
+
+pub fn expr_sexp(e: &Expr) -> Sexp {
+    match e {
+        Expr::Value(v) => value_sexp(v),
+        Expr::Prim(p) => {
+            let (op, l, r) = match p {
+                Prim::Add(l, r) => ("plus", l, r),
+                Prim::Sub(l, r) => ("minus", l, r),
+                Prim::Mul(l, r) => ("times", l, r),
+                Prim::LessThan(l, r) => ("lt", l, r)
+            };
+            Sexp::node(op, vec![expr_sexp(l), expr_sexp(r)])
+        }
+        Expr::IfThenElse(c, t, f) => Sexp::node("if", vec![expr_sexp(c), expr_sexp(t), expr_sexp(f)]),
+        Expr::Ident(x) => Sexp::node("var", vec![Sexp::atom(x)]),
+        Expr::Let(x, e1, e2) => Sexp::node("let", vec![Sexp::atom(x), expr_sexp(e1), expr_sexp(e2)]),
+        Expr::Fun(x, e) => Sexp::node("fun", vec![Sexp::atom(x), expr_sexp(e)]),
+        Expr::App(e1, e2) => Sexp::node("app", vec![expr_sexp(e1), expr_sexp(e2)]),
+        Expr::LetRec(f, x, e1, e2) => Sexp::node("letrec", vec![Sexp::atom(f), Sexp::atom(x), expr_sexp(e1), expr_sexp(e2)]),
+        Expr::Cons(l, r) => Sexp::node("cons", vec![expr_sexp(l), expr_sexp(r)]),
+        Expr::Match(e, clauses) => {
+            let mut items = vec![expr_sexp(e)];
+            items.extend(clauses.iter().map(|(p, e)| Sexp::List(vec![pattern_sexp(p), expr_sexp(e)])));
+            Sexp::node("match", items)
+        }
+        Expr::Index(i) => Sexp::node("index", vec![Sexp::Atom(i.to_string())]),
+        Expr::NamelessLet(e1, e2) => Sexp::node("nameless-let", vec![expr_sexp(e1), expr_sexp(e2)]),
+        Expr::NamelessFun(e) => Sexp::node("nameless-fun", vec![expr_sexp(e)]),
+        Expr::NamelessLetRec(e1, e2) => Sexp::node("nameless-letrec", vec![expr_sexp(e1), expr_sexp(e2)]),
+        Expr::Hole => Sexp::atom("_"),
+        Expr::LetCc(k, e) => Sexp::node("letcc", vec![Sexp::atom(k), expr_sexp(e)]),
+        Expr::Ref(e) => Sexp::node("ref", vec![expr_sexp(e)]),
+        Expr::Deref(e) => Sexp::node("deref", vec![expr_sexp(e)]),
+        Expr::Assign(e1, e2) => Sexp::node("assign", vec![expr_sexp(e1), expr_sexp(e2)])
+    }
+}
+
+fn pattern_sexp(p: &Pattern) -> Sexp {
+    match p {
+        Pattern::Var(x) => Sexp::node("var", vec![Sexp::atom(x)]),
+        Pattern::Nil => Sexp::atom("nil"),
+        Pattern::Cons(l, r) => Sexp::node("cons", vec![pattern_sexp(l), pattern_sexp(r)]),
+        Pattern::Wild => Sexp::atom("_")
+    }
+}
+
+// 整数と真偽値はそのまま書く
+pub fn value_sexp(v: &Value) -> Sexp {
+    match v {
+        Value::Int(i) => Sexp::Atom(i.to_string()),
+        Value::Bool(b) => Sexp::Atom(b.to_string()),
+        Value::Closure(env, x, e) => Sexp::node("closure", vec![env_sexp(env), Sexp::atom(x), expr_sexp(e)]),
+        Value::RecClosure(env, f, x, e) => Sexp::node("rec-closure", vec![env_sexp(env), Sexp::atom(f), Sexp::atom(x), expr_sexp(e)]),
+        Value::Nil => Sexp::atom("nil"),
+        Value::Cons(l, r) => Sexp::node("cons", vec![value_sexp(l), value_sexp(r)]),
+        Value::NamelessClosure(env, e) => Sexp::node("nameless-closure", vec![nameless_env_sexp(env), expr_sexp(e)]),
+        Value::NamelessRecClosure(env, e) => Sexp::node("nameless-rec-closure", vec![nameless_env_sexp(env), expr_sexp(e)]),
+        Value::Cont(k) => cont_sexp(k),
+        Value::Loc(l) => Sexp::Atom(format!("@{}", l))
+    }
+}
+
+// 環境は書いた順 (古い変数が先)
+pub fn env_sexp(env: &Env) -> Sexp {
+    Sexp::node("env", env.0.iter().rev().map(|EnvVar(x, e)| Sexp::List(vec![Sexp::atom(x), expr_sexp(e)])).collect())
+}
+
+pub fn vars_sexp(vars: &Vars) -> Sexp {
+    Sexp::node("vars", vars.0.iter().rev().map(|x| Sexp::atom(x)).collect())
+}
+
+pub fn nameless_env_sexp(env: &NamelessEnv) -> Sexp {
+    Sexp::node("env", env.0.iter().rev().map(value_sexp).collect())
+}
+
+pub fn store_sexp(store: &Store) -> Sexp {
+    Sexp::node("store", store.0.iter().map(|(l, v)| Sexp::List(vec![Sexp::Atom(format!("@{}", l)), value_sexp(v)])).collect())
+}
+
+pub fn type_env_sexp(env: &TypeEnv) -> Sexp {
+    Sexp::node("env", env.0.iter().rev().map(|(x, s)| Sexp::List(vec![Sexp::atom(x), scheme_sexp(s)])).collect())
+}
+
+fn scheme_sexp(s: &Scheme) -> Sexp {
+    if s.0.is_empty() {
+        return type_sexp(&s.1)
+    }
+    let vars = s.0.iter().map(|n| Sexp::Atom(Type::Var(*n).to_string())).collect();
+    Sexp::node("forall", vec![Sexp::List(vars), type_sexp(&s.1)])
+}
+
+pub fn type_sexp(t: &Type) -> Sexp {
+    match t {
+        Type::Fun(l, r) => Sexp::node("->", vec![type_sexp(l), type_sexp(r)]),
+        Type::List(t) => Sexp::node("list", vec![type_sexp(t)]),
+        t => Sexp::Atom(t.to_string())
+    }
+}
+
+// 先頭が次に使われるフレーム
+pub fn cont_sexp(k: &Cont) -> Sexp {
+    Sexp::node("cont", k.0.iter().map(|Frame(env, e)| match env {
+        Some(env) => Sexp::node("frame", vec![env_sexp(env), expr_sexp(e)]),
+        None => Sexp::node("frame", vec![expr_sexp(e)])
+    }).collect())
+}
@@ -1,30 +1,106 @@
-use crate::expr::{Expr, Prim, Value, Unary, EnvVar, Env, Form};
+use crate::expr::{Expr, Prim, Value, EnvVar, Env, Form, Pattern};
+use crate::derivation::Derivation;
 
 use std::fmt;
 use std::ops;
+use std::str;
 
 // BNF
 // EvalML1
 // i ∈ int
 // b ∈ {true, false}
-// x, y ∈ Var
 // v ∈ Value ::= i | b
-// ε ∈ ∅ | ε , x = v
 // r ∈ Res ::= v | error
-// e ∈ Exp ::= i | b | e op e | if e then e else e | (e) | let x = e in e
+// e ∈ Exp ::= i | b | e op e | if e then e else e | (e)
 // op ∈ Prim ::= + | - | * | <
+//
+// EvalML2
+// x, y ∈ Var
+// ε ∈ ∅ | ε , x = v
+// e ∈ Exp ::= ... | x | let x = e in e
+//
+// EvalML3
+// v ∈ Value ::= i | b | (ε)[fun x -> e] | (ε)[rec x = fun y -> e]
+// e ∈ Exp ::= ... | fun x -> e | e e | let rec x = fun y -> e in e
+//
+// EvalML4
+// v ∈ Value ::= ... | [] | v :: v
+// e ∈ Exp ::= ... | [] | e :: e | match e with [] -> e | x :: y -> e
+//
+// EvalML5
+// p ∈ Pat ::= x | [] | p :: p | _
+// c ∈ Clauses ::= p -> e | p -> e '|' c
+// e ∈ Exp ::= ... | match e with c
+
+// 同じ式でも体系によって使う規則が異なる
+// EvalML1, EvalML2 の規則名は EvalML3 と同じ
+// EvalML1 には環境がないので判断に |- を書かない
+//   EvalML3: E-Var1, E-Var2
+//   EvalML4: E-Var, E-MatchNil, E-MatchCons
+//   EvalML5: E-Var, E-MatchM1, E-MatchM2, E-MatchN
+// NamelessML3 は評価ではなく名前なし式への変換, EvalNamelessML3 は名前なし式の評価 (nameless.rs)
+// EvalContML1, EvalContML4 は継続を使った評価 (cont.rs)
+// EvalRefML3 はストアを使った参照の評価 (reference.rs)
+// TypingML4, PolyTypingML4 は評価ではなく型推論 (typing.rs)
+// Nat, CompareNat1..3, EvalNatExp, ReduceNatExp は導出の検査だけ (nat.rs, check.rs)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum System {
+    EvalML1,
+    EvalML3,
+    EvalML4,
+    EvalML5,
+    NamelessML3,
+    EvalNamelessML3,
+    EvalContML1,
+    EvalContML4,
+    EvalRefML3,
+    TypingML4,
+    PolyTypingML4,
+    Nat,
+    CompareNat1,
+    CompareNat2,
+    CompareNat3,
+    EvalNatExp,
+    ReduceNatExp
+}
+
+impl str::FromStr for System {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> anyhow::Result<System> {
+        match s {
+            "EvalML1" => Ok(System::EvalML1),
+            "EvalML2" | "EvalML3" => Ok(System::EvalML3),
+            "EvalML4" => Ok(System::EvalML4),
+            "EvalML5" => Ok(System::EvalML5),
+            "NamelessML3" => Ok(System::NamelessML3),
+            "EvalNamelessML3" => Ok(System::EvalNamelessML3),
+            "EvalContML1" => Ok(System::EvalContML1),
+            "EvalContML4" => Ok(System::EvalContML4),
+            "EvalRefML3" => Ok(System::EvalRefML3),
+            "TypingML4" => Ok(System::TypingML4),
+            "PolyTypingML4" => Ok(System::PolyTypingML4),
+            "Nat" => Ok(System::Nat),
+            "CompareNat1" => Ok(System::CompareNat1),
+            "CompareNat2" => Ok(System::CompareNat2),
+            "CompareNat3" => Ok(System::CompareNat3),
+            "EvalNatExp" => Ok(System::EvalNatExp),
+            "ReduceNatExp" => Ok(System::ReduceNatExp),
+            _ => Err(anyhow::anyhow!("unknown system: {}", s))
+        }
+    }
+}
 
 // const OPERATORS: [&str; 4] = [
 //     "+","-", "*", "<"
 // ];
 
 #[derive(Debug)]
-pub enum EvalResult {
-    Value(Value),
+pub enum EvalResult<'a> {
+    Value(Value<'a>),
     Err(EvalError)
 }
 
-impl fmt::Display for EvalResult {
+impl<'a> fmt::Display for EvalResult<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             EvalResult::Value(v) => write!(f, "{}", v),
@@ -55,10 +131,11 @@ pub enum EvalError {
     TimesErrorR,
     LtErrorL,
     LtErrorR,
-    UnaryMinusBool,
     LetError1,
     LetError2,
-    VarErr
+    VarErr,
+    // 規則のない誤り (関数適用, let rec, リスト, match)
+    Stuck
 }
 
 impl fmt::Display for EvalError {
@@ -84,76 +161,105 @@ impl fmt::Display for EvalError {
             EvalError::TimesErrorR => write!(f, "E-TimesErrorR"),
             EvalError::LtErrorL => write!(f, "E-LtErrorL"),
             EvalError::LtErrorR => write!(f, "E-LtErrorR"),
-            EvalError::UnaryMinusBool => write!(f, "E-UnaryMinusBool"),
             EvalError::LetError1 => write!(f, "E-LetError1"),
             EvalError::LetError2 => write!(f, "E-LetError2"),
-            EvalError::VarErr => write!(f, "E-VarErr")
+            EvalError::VarErr => write!(f, "E-VarErr"),
+            EvalError::Stuck => write!(f, "stuck")
         }
     }
 }
 
-impl ops::Add<EvalResult> for EvalResult {
-    type Output = EvalResult;
-    fn add(self, rhs: EvalResult) -> EvalResult {
+impl<'a> ops::Add<EvalResult<'a>> for EvalResult<'a> {
+    type Output = EvalResult<'a>;
+    fn add(self, rhs: EvalResult<'a>) -> EvalResult<'a> {
         match (self, rhs) {
             (EvalResult::Value(Value::Int(l)), EvalResult::Value(Value::Int(r))) =>
                 EvalResult::Value(Value::Int(l + r)),
             (EvalResult::Value(Value::Bool(_)), _) => EvalResult::Err(EvalError::PlusBoolL),
             (_, EvalResult::Value(Value::Bool(_))) => EvalResult::Err(EvalError::PlusBoolR),
             (EvalResult::Err(_), _) => EvalResult::Err(EvalError::PlusErrorL),
-            (_, EvalResult::Err(_)) => EvalResult::Err(EvalError::PlusErrorR)
+            (_, EvalResult::Err(_)) => EvalResult::Err(EvalError::PlusErrorR),
+            // クロージャは bool と同じく整数でない値として扱う
+            (EvalResult::Value(Value::Int(_)), _) => EvalResult::Err(EvalError::PlusBoolR),
+            _ => EvalResult::Err(EvalError::PlusBoolL)
         }
     }
 }
 
-impl ops::Sub<EvalResult> for EvalResult {
-    type Output = EvalResult;
-    fn sub(self, rhs: EvalResult) -> EvalResult {
+impl<'a> ops::Sub<EvalResult<'a>> for EvalResult<'a> {
+    type Output = EvalResult<'a>;
+    fn sub(self, rhs: EvalResult<'a>) -> EvalResult<'a> {
         match (self, rhs) {
             (EvalResult::Value(Value::Int(l)), EvalResult::Value(Value::Int(r))) =>
                 EvalResult::Value(Value::Int(l - r)),
             (EvalResult::Value(Value::Bool(_)), _) => EvalResult::Err(EvalError::MinusBoolL),
             (_, EvalResult::Value(Value::Bool(_))) => EvalResult::Err(EvalError::MinusBoolR),
             (EvalResult::Err(_), _) => EvalResult::Err(EvalError::MinusErrorL),
-            (_, EvalResult::Err(_)) => EvalResult::Err(EvalError::MinusErrorR)
+            (_, EvalResult::Err(_)) => EvalResult::Err(EvalError::MinusErrorR),
+            // クロージャは bool と同じく整数でない値として扱う
+            (EvalResult::Value(Value::Int(_)), _) => EvalResult::Err(EvalError::MinusBoolR),
+            _ => EvalResult::Err(EvalError::MinusBoolL)
        }
     }
 }
 
-impl ops::Mul<EvalResult> for EvalResult {
-    type Output = EvalResult;
-    fn mul(self, rhs: EvalResult) -> EvalResult {
+impl<'a> ops::Mul<EvalResult<'a>> for EvalResult<'a> {
+    type Output = EvalResult<'a>;
+    fn mul(self, rhs: EvalResult<'a>) -> EvalResult<'a> {
         match (self, rhs) {
             (EvalResult::Value(Value::Int(l)), EvalResult::Value(Value::Int(r))) => EvalResult::Value(Value::Int(l * r)),
             (EvalResult::Value(Value::Bool(_)), _) => EvalResult::Err(EvalError::TimesBoolL),
             (_, EvalResult::Value(Value::Bool(_))) => EvalResult::Err(EvalError::TimesBoolR),
             (EvalResult::Err(_), _) => EvalResult::Err(EvalError::TimesErrorL),
-            (_, EvalResult::Err(_)) => EvalResult::Err(EvalError::TimesErrorR)
+            (_, EvalResult::Err(_)) => EvalResult::Err(EvalError::TimesErrorR),
+            // クロージャは bool と同じく整数でない値として扱う
+            (EvalResult::Value(Value::Int(_)), _) => EvalResult::Err(EvalError::TimesBoolR),
+            _ => EvalResult::Err(EvalError::TimesBoolL)
         }
     }
 }
 
-impl ops::Neg for EvalResult {
-    type Output = EvalResult;
-    fn neg(self) -> EvalResult {
-        match self {
-            EvalResult::Value(Value::Int(i)) => EvalResult::Value(Value::Int(-i)),
-            EvalResult::Value(Value::Bool(_)) => EvalResult::Err(EvalError::UnaryMinusBool),
-            e @ EvalResult::Err(_) => e
-        }
-    }
+pub fn solve(form: &Form, sys: System) -> anyhow::Result<()> {
+    print!("{}", derive(form, sys)?);
+    Ok(())
 }
 
-pub fn solve(form: &Form) {
+pub fn derive(form: &Form, sys: System) -> anyhow::Result<Derivation> {
     let env = &form.0;
     let expr = &form.1;
-    apply_rule(env, expr);
+    if sys == System::EvalML1 {
+        ml1_form(form)?;
+    }
+    apply_rule(sys, env, expr)
+}
+
+// EvalML1 には環境も変数もない
+pub fn ml1_form(form: &Form) -> anyhow::Result<()> {
+    if !form.0.0.is_empty() {
+        return Err(anyhow::anyhow!("EvalML1 has no environment; use EvalML2"))
+    }
+    ml1_expr(&form.1)
 }
 
-fn eval(env: &Env, expr: &Expr) -> EvalResult {
+fn ml1_expr(expr: &Expr) -> anyhow::Result<()> {
+    match expr {
+        Expr::Value(Value::Int(_) | Value::Bool(_)) => Ok(()),
+        Expr::Prim(Prim::Add(l, r) | Prim::Sub(l, r) | Prim::Mul(l, r) | Prim::LessThan(l, r)) => {
+            ml1_expr(l)?;
+            ml1_expr(r)
+        }
+        Expr::IfThenElse(cond, then, els) => {
+            ml1_expr(cond)?;
+            ml1_expr(then)?;
+            ml1_expr(els)
+        }
+        _ => Err(anyhow::anyhow!("EvalML1 has no `{}`; use EvalML2 or EvalML3", expr))
+    }
+}
+
+fn eval<'a>(env: &Env<'a>, expr: &Expr<'a>) -> EvalResult<'a> {
     match expr {
         Expr::Value(x) => EvalResult::Value(x.clone()),
-        Expr::Unary(_) => -eval(env, expr),
         Expr::Ident(name) => {
             match get_env_var(env, name) {
                 Some(v) => EvalResult::Value(v.clone()),
@@ -174,14 +280,16 @@ fn eval(env: &Env, expr: &Expr) -> EvalResult {
                         (EvalResult::Value(Value::Bool(_)), _) => EvalResult::Err(EvalError::LtBoolL),
                         (_, EvalResult::Value(Value::Bool(_))) => EvalResult::Err(EvalError::LtBoolR),
                         (EvalResult::Err(_), _) => EvalResult::Err(EvalError::LtErrorL),
-                        (_, EvalResult::Err(_)) => EvalResult::Err(EvalError::LtErrorR)
+                        (_, EvalResult::Err(_)) => EvalResult::Err(EvalError::LtErrorR),
+                        // クロージャは bool と同じく整数でない値として扱う
+                        (EvalResult::Value(Value::Int(_)), _) => EvalResult::Err(EvalError::LtBoolR),
+                        _ => EvalResult::Err(EvalError::LtBoolL)
                     }
                 },
             }
         }
         Expr::IfThenElse(cond, then, els) => {
             match eval(env, cond) {
-                EvalResult::Value(Value::Int(_)) => EvalResult::Err(EvalError::IfInt),
                 EvalResult::Value(Value::Bool(b)) => {
                     if b {
                         match eval(env, then) {
@@ -190,12 +298,13 @@ fn eval(env: &Env, expr: &Expr) -> EvalResult {
                         }
                     } else {
                         match eval(env, els) {
-                            EvalResult::Err(_) => EvalResult::Err(EvalError::IfTError),
+                            EvalResult::Err(_) => EvalResult::Err(EvalError::IfFError),
                             res => res
                         }
                     }
                 }
-                e @ EvalResult::Err(_) => e
+                EvalResult::Value(_) => EvalResult::Err(EvalError::IfInt),
+                EvalResult::Err(_) => EvalResult::Err(EvalError::IfError)
             }
         }
         Expr::Let(var, var_exp, expr) => {
@@ -203,7 +312,7 @@ fn eval(env: &Env, expr: &Expr) -> EvalResult {
             match var_exp_evaled {
                 EvalResult::Value(v) => {
                     let val = Expr::Value(v);
-                    let new_env = Env(vec![EnvVar(var, box val)]);
+                    let new_env = Env(vec![EnvVar(var, Box::new(val))]);
                     if let result @ EvalResult::Value(_) = eval(&new_env.appended(env), expr) {
                         result
                     } else {
@@ -215,156 +324,304 @@ fn eval(env: &Env, expr: &Expr) -> EvalResult {
                 }
             }
         }
-    }
-}
-
-fn apply_rule(env: &Env, expr: &Expr) {
-    let evaled = eval(env, expr);
-    match expr {
-        Expr::Value(Value::Int(i)) => println!("{} {} evalto {} by E-Int {{}};", env.form(), i, i),
-        Expr::Value(Value::Bool(b)) => println!("{} {} evalto {} by E-Bool {{}};", env.form(), b, b),
-        Expr::Unary(Unary::Minus(_)) => println!("{} {} evalto {} by E-Int {{}};", env.form(), expr, evaled),
-        Expr::Prim(Prim::Add(l, r)) => {
-            let evaled = eval(env, expr);
-            println!("{} {} evalto {} by E-Plus {{", env.form(), expr, evaled);
-            match evaled {
-                EvalResult::Value(v) => {
-                    apply_rule(env, l);
-                    apply_rule(env, r);
-                    println!("{} plus {} is {} by B-Plus {{}};", eval(env, l), eval(env, r), v);
+        Expr::Fun(x, body) => EvalResult::Value(Value::Closure(env.clone(), x, body.clone())),
+        Expr::App(fun, arg) => {
+            match (eval(env, fun), eval(env, arg)) {
+                (EvalResult::Value(closure), EvalResult::Value(v)) => {
+                    match app_env(&closure, v) {
+                        Some((new_env, body)) => {
+                            match eval(&new_env, &body) {
+                                EvalResult::Err(_) => EvalResult::Err(EvalError::Stuck),
+                                res => res
+                            }
+                        }
+                        None => EvalResult::Err(EvalError::Stuck)
+                    }
                 }
-                EvalResult::Err(EvalError::PlusBoolL) => apply_rule(env, l),
-                EvalResult::Err(EvalError::PlusBoolR) => apply_rule(env, r),
-                EvalResult::Err(EvalError::PlusErrorL) => apply_rule(env, l),
-                EvalResult::Err(EvalError::PlusErrorR) => apply_rule(env, r),
-                _ => unreachable!("internal: unreachable point apply_rule: Add")
+                _ => EvalResult::Err(EvalError::Stuck)
             }
-            println!("}};");
         }
-        Expr::Prim(Prim::Sub(l, r)) => {
-            println!("{} {} evalto {} by E-Minus {{", env.form(), expr, evaled);
-                match evaled {
-                EvalResult::Value(v) => {
-                    apply_rule(env, l);
-                    apply_rule(env, r);
-                    println!("{} minus {} is {} by B-Minus {{}};", eval(env, l), eval(env, r), v);
-                }
-                EvalResult::Err(EvalError::MinusBoolL) => apply_rule(env, l),
-                EvalResult::Err(EvalError::MinusBoolR) => apply_rule(env, r),
-                EvalResult::Err(EvalError::MinusErrorL) => apply_rule(env, l),
-                EvalResult::Err(EvalError::MinusErrorR) => apply_rule(env, r),
-                _ => unreachable!("internal: unreachable point apply_rule: Minus")
+        Expr::LetRec(fun, x, fun_exp, expr) => {
+            let new_env = rec_env(env, fun, x, fun_exp);
+            match eval(&new_env, expr) {
+                EvalResult::Err(_) => EvalResult::Err(EvalError::Stuck),
+                res => res
             }
-            println!("}};");
         }
-        Expr::Prim(Prim::Mul(l, r)) => {
-            println!("{} {} evalto {} by E-Times {{", env.form(), expr, evaled);
-            match evaled {
-                EvalResult::Value(v) => {
-                    apply_rule(env, l);
-                    apply_rule(env, r);
-                    println!("{} times {} is {} by B-Times {{}};", eval(env, l), eval(env, r), v);
-                }
-                EvalResult::Err(EvalError::TimesBoolL) => apply_rule(env, l),
-                EvalResult::Err(EvalError::TimesBoolR) => apply_rule(env, r),
-                EvalResult::Err(EvalError::TimesErrorL) => apply_rule(env, l),
-                EvalResult::Err(EvalError::TimesErrorR) => apply_rule(env, r),
-                _ => unreachable!("internal: unreachable point apply_rule: Times")
+        Expr::Cons(l, r) => {
+            match (eval(env, l), eval(env, r)) {
+                (EvalResult::Value(l), EvalResult::Value(r)) => EvalResult::Value(Value::Cons(Box::new(l), Box::new(r))),
+                _ => EvalResult::Err(EvalError::Stuck)
             }
-            println!("}};");
         }
-        Expr::Prim(Prim::LessThan(l, r)) => {
-            println!("{} {} evalto {} by E-Lt {{", env.form(), expr, evaled);
-            match evaled {
-                EvalResult::Value(v) => {
-                    apply_rule(env, l);
-                    apply_rule(env, r);
-                    println!("{} less than {} is {} by B-Lt {{}};", eval(env, l), eval(env, r), v);
+        Expr::Match(e0, clauses) => {
+            let v = match eval(env, e0) {
+                EvalResult::Value(v) => v,
+                EvalResult::Err(_) => return EvalResult::Err(EvalError::Stuck)
+            };
+            for (p, e) in clauses {
+                if let Some(matched) = pattern_match(p, &v) {
+                    return match eval(&matched.appended(env), e) {
+                        EvalResult::Err(_) => EvalResult::Err(EvalError::Stuck),
+                        res => res
+                    }
                 }
-                EvalResult::Err(EvalError::LtBoolL) => apply_rule(env, l),
-                EvalResult::Err(EvalError::LtBoolR) => apply_rule(env, r),
-                EvalResult::Err(EvalError::LtErrorL) => apply_rule(env, l),
-                EvalResult::Err(EvalError::LtErrorR) => apply_rule(env, r),
-                _ => unreachable!("internal: unreachable point apply_rule: Lt")
             }
-            println!("}};");
+            EvalResult::Err(EvalError::Stuck)
+        }
+        Expr::Index(_) | Expr::NamelessLet(_, _) | Expr::NamelessFun(_) | Expr::NamelessLetRec(_, _) =>
+            unreachable!("internal: nameless expressions are not evaluated by eval"),
+        Expr::Hole | Expr::LetCc(_, _) => unreachable!("internal: holes and letcc appear only in EvalContML"),
+        Expr::Ref(_) | Expr::Deref(_) | Expr::Assign(_, _) => unreachable!("internal: references appear only in EvalRefML3")
+    }
+}
+
+// p matches v when (ε)
+// 束縛は左から順に並ぶ (先頭が一番新しい)
+fn pattern_match<'a>(p: &Pattern<'a>, v: &Value<'a>) -> Option<Env<'a>> {
+    match (p, v) {
+        (Pattern::Var(x), v) => Some(Env(vec![EnvVar(x, Box::new(Expr::Value(v.clone())))])),
+        (Pattern::Wild, _) => Some(Env(vec![])),
+        (Pattern::Nil, Value::Nil) => Some(Env(vec![])),
+        (Pattern::Cons(p1, p2), Value::Cons(v1, v2)) => {
+            let env1 = pattern_match(p1, v1)?;
+            let env2 = pattern_match(p2, v2)?;
+            Some(env2.appended(&env1))
+        }
+        _ => None
+    }
+}
+
+// 関数本体を評価する環境を作る
+// E-App: ε2, x = v2
+// E-AppRec: ε2, f = (ε2)[rec f = fun x -> e0], x = v2
+fn app_env<'a>(closure: &Value<'a>, arg: Value<'a>) -> Option<(Env<'a>, Expr<'a>)> {
+    match closure {
+        Value::Closure(env, x, body) => {
+            let new_env = Env(vec![EnvVar(x, Box::new(Expr::Value(arg)))]);
+            Some((new_env.appended(env), *body.clone()))
+        }
+        Value::RecClosure(env, fun, x, body) => {
+            let new_env = Env(vec![
+                EnvVar(x, Box::new(Expr::Value(arg))),
+                EnvVar(fun, Box::new(Expr::Value(closure.clone())))
+            ]);
+            Some((new_env.appended(env), *body.clone()))
+        }
+        _ => None
+    }
+}
+
+fn rec_env<'a>(env: &Env<'a>, fun: &'a String, x: &'a String, fun_exp: &Expr<'a>) -> Env<'a> {
+    let closure = Value::RecClosure(env.clone(), fun, x, Box::new(fun_exp.clone()));
+    let new_env = Env(vec![EnvVar(fun, Box::new(Expr::Value(closure)))]);
+    new_env.appended(env)
+}
+
+// 導出を組み立てる
+// 判断の先頭は環境 (空なら " |-", EvalML1 では何も書かない)
+fn apply_rule<'a>(sys: System, env: &Env<'a>, expr: &Expr<'a>) -> anyhow::Result<Derivation> {
+    let evaled = eval(env, expr);
+    let judge = |e: &dyn fmt::Display, r: &dyn fmt::Display| match sys {
+        System::EvalML1 if env.0.is_empty() => format!("{} evalto {}", e, r),
+        _ => format!("{} {} evalto {}", env.form(), e, r)
+    };
+    // EvalML4 と EvalML5 には誤りの規則がない
+    if matches!(sys, System::EvalML4 | System::EvalML5) && matches!(evaled, EvalResult::Err(_)) {
+        return Err(anyhow::anyhow!("{:?} has no error rules: {}", sys, expr))
+    }
+    let d = match expr {
+        Expr::Value(Value::Int(i)) => Derivation::new(judge(i, i), "E-Int", vec![]),
+        Expr::Value(Value::Bool(b)) => Derivation::new(judge(b, b), "E-Bool", vec![]),
+        Expr::Value(Value::Nil) => Derivation::new(judge(&"[]", &"[]"), "E-Nil", vec![]),
+        Expr::Value(_) => unreachable!("internal: closures are not written in expressions"),
+        Expr::Prim(p) => {
+            let (l, r, rule, op) = match p {
+                Prim::Add(l, r) => (l, r, "Plus", "plus"),
+                Prim::Sub(l, r) => (l, r, "Minus", "minus"),
+                Prim::Mul(l, r) => (l, r, "Times", "times"),
+                Prim::LessThan(l, r) => (l, r, "Lt", "less than")
+            };
+            let premises = match &evaled {
+                EvalResult::Value(v) => vec![
+                    apply_rule(sys, env, l)?,
+                    apply_rule(sys, env, r)?,
+                    Derivation::new(format!("{} {} {} is {}", eval(env, l), op, eval(env, r), v), &format!("B-{}", rule), vec![])
+                ],
+                EvalResult::Err(
+                    EvalError::PlusBoolL | EvalError::PlusErrorL | EvalError::MinusBoolL | EvalError::MinusErrorL
+                    | EvalError::TimesBoolL | EvalError::TimesErrorL | EvalError::LtBoolL | EvalError::LtErrorL
+                ) => vec![apply_rule(sys, env, l)?],
+                EvalResult::Err(
+                    EvalError::PlusBoolR | EvalError::PlusErrorR | EvalError::MinusBoolR | EvalError::MinusErrorR
+                    | EvalError::TimesBoolR | EvalError::TimesErrorR | EvalError::LtBoolR | EvalError::LtErrorR
+                ) => vec![apply_rule(sys, env, r)?],
+                _ => unreachable!("internal: unreachable point apply_rule: {}", rule)
+            };
+            // エラーになる場合は E-PlusBoolL などの規則
+            let rule = match &evaled {
+                EvalResult::Err(e) => e.to_string(),
+                EvalResult::Value(_) => format!("E-{}", rule)
+            };
+            Derivation::new(judge(expr, &evaled), &rule, premises)
         }
         Expr::IfThenElse(cond, then, els) => {
-            match evaled {
-                EvalResult::Value(v) => {
-                    let cond_result = eval(env, cond);
-                    if let EvalResult::Value(Value::Bool(true)) = cond_result {
-                        println!("{} {} evalto {} by E-IfT {{", env.form(), expr, v);
-                        apply_rule(env, cond);
-                        apply_rule(env, then);
-                    } else {
-                        println!("{} {} evalto {} by E-IfF {{", env.form(), expr, v);
-                        apply_rule(env, cond);
-                        apply_rule(env, els);
-                    }
-                }
-                EvalResult::Err(EvalError::IfError) => apply_rule(env, cond),
-                EvalResult::Err(EvalError::IfInt) => {
-                    println!("{} {} evalto {} by E-IfInt {{", env.form(), expr, evaled);
-                    apply_rule(env, cond);
-                },
-                EvalResult::Err(EvalError::IfTError) => {
-                    println!("{} {} evalto {} by E-IfT {{", env.form(), expr, evaled);
-                    apply_rule(env, cond);
-                    apply_rule(env, then);
-                }
-                EvalResult::Err(EvalError::IfFError) => {
-                    println!("{} {} evalto {} by E-IfF {{", env.form(), expr, evaled);
-                    apply_rule(env, cond);
-                    apply_rule(env, els);
+            let branch = |b: bool| -> anyhow::Result<(&str, Vec<Derivation>)> {
+                match b {
+                    true => Ok(("E-IfT", vec![apply_rule(sys, env, cond)?, apply_rule(sys, env, then)?])),
+                    false => Ok(("E-IfF", vec![apply_rule(sys, env, cond)?, apply_rule(sys, env, els)?]))
                 }
+            };
+            let (rule, premises) = match evaled {
+                EvalResult::Value(_) => branch(matches!(eval(env, cond), EvalResult::Value(Value::Bool(true))))?,
+                EvalResult::Err(EvalError::IfError) => ("E-IfError", vec![apply_rule(sys, env, cond)?]),
+                EvalResult::Err(EvalError::IfInt) => ("E-IfInt", vec![apply_rule(sys, env, cond)?]),
+                EvalResult::Err(EvalError::IfTError) => ("E-IfTError", branch(true)?.1),
+                EvalResult::Err(EvalError::IfFError) => ("E-IfFError", branch(false)?.1),
                 _ => unreachable!("internal: unreachable point apply_rule: IfThenElse")
-            }
-
-            println!("}};");
+            };
+            Derivation::new(judge(expr, &evaled), rule, premises)
         }
         Expr::Ident(name) => {
-            match evaled {
-                EvalResult::Value(v) => {
+            match &evaled {
+                EvalResult::Value(_) if !matches!(sys, System::EvalML1 | System::EvalML3) => Derivation::new(judge(name, &evaled), "E-Var", vec![]),
+                EvalResult::Value(_) => {
                     match env.0.first() {
-                        Some(EnvVar(n, _)) => {
-                            if n == name {
-                                println!("{} {} evalto {} by E-Var1 {{}};", env.form(), name, v);
-                            } else {
-                                println!("{} {} evalto {} by E-Var2 {{", env.form(), name, v);
-                                apply_rule(&Env(env.0[1..env.0.len()].to_vec()), expr);
-                                println!("}};");
-                            }
+                        Some(EnvVar(n, _)) if n == name => Derivation::new(judge(name, &evaled), "E-Var1", vec![]),
+                        Some(_) => {
+                            let premise = apply_rule(sys, &Env(env.0[1..env.0.len()].to_vec()), expr)?;
+                            Derivation::new(judge(name, &evaled), "E-Var2", vec![premise])
                         }
                         None => unreachable!("solver Ident: unreachable")
                     }
                 }
-                _ => unimplemented!("unimplemented ident errors")
+                EvalResult::Err(EvalError::VarErr) => Derivation::new(judge(name, &evaled), "E-VarErr", vec![]),
+                _ => unreachable!("internal: unreachable point apply_rule: Ident")
             }
         }
         Expr::Let(var, var_exp, exp) => {
-            println!("{} {} evalto {} by E-Let {{", env.form(), expr, evaled);
-            match evaled {
-                EvalResult::Value(_) => {
-                    apply_rule(env, var_exp);
-                    let var_exp_evaled = eval(env, var_exp);
-                    if let EvalResult::Value(v) = var_exp_evaled {
-                        let val = Expr::Value(v);
-                        let new_env = Env(vec![EnvVar(var, box val)]);
-                        let new_env = new_env.appended(env);
-                        apply_rule(&new_env, exp)
-                    } else {
-                        println!("unexpected var_exp")
+            let rule = match evaled {
+                EvalResult::Value(_) => "E-Let",
+                EvalResult::Err(EvalError::LetError1) => "E-LetError1",
+                EvalResult::Err(EvalError::LetError2) => "E-LetError2",
+                _ => unreachable!("internal: unreachable point apply_rule: Let")
+            };
+            let mut premises = vec![apply_rule(sys, env, var_exp)?];
+            if let EvalResult::Value(v) = eval(env, var_exp) {
+                let val = Expr::Value(v);
+                let new_env = Env(vec![EnvVar(var, Box::new(val))]);
+                let new_env = new_env.appended(env);
+                premises.push(apply_rule(sys, &new_env, exp)?);
+            }
+            Derivation::new(judge(expr, &evaled), rule, premises)
+        }
+        Expr::Fun(_, _) => Derivation::new(judge(expr, &evaled), "E-Fun", vec![]),
+        Expr::App(fun, arg) => {
+            let closure = eval(env, fun);
+            let rule = match (&evaled, &closure) {
+                (EvalResult::Err(_), _) => return Err(anyhow::anyhow!("{:?} has no error rule for application: {}", sys, expr)),
+                (_, EvalResult::Value(Value::RecClosure(_, _, _, _))) => "E-AppRec",
+                _ => "E-App"
+            };
+            let mut premises = vec![apply_rule(sys, env, fun)?];
+            if let EvalResult::Value(closure) = closure {
+                premises.push(apply_rule(sys, env, arg)?);
+                if let EvalResult::Value(v) = eval(env, arg) {
+                    if let Some((new_env, body)) = app_env(&closure, v) {
+                        premises.push(apply_rule(sys, &new_env, &body)?);
                     }
                 }
-                _ => unimplemented!("unimplemented let errors")
             }
-            println!("}};");
+            Derivation::new(judge(expr, &evaled), rule, premises)
         }
-    }
+        Expr::LetRec(fun, x, fun_exp, exp) => {
+            let rule = match evaled {
+                EvalResult::Value(_) => "E-LetRec",
+                EvalResult::Err(_) => return Err(anyhow::anyhow!("{:?} has no error rule for let rec: {}", sys, expr))
+            };
+            let premise = apply_rule(sys, &rec_env(env, fun, x, fun_exp), exp)?;
+            Derivation::new(judge(expr, &evaled), rule, vec![premise])
+        }
+        Expr::Cons(l, r) => {
+            let rule = match evaled {
+                EvalResult::Value(_) => "E-Cons",
+                EvalResult::Err(_) => return Err(anyhow::anyhow!("{:?} has no error rule for lists: {}", sys, expr))
+            };
+            let mut premises = vec![apply_rule(sys, env, l)?];
+            if let EvalResult::Value(_) = eval(env, l) {
+                premises.push(apply_rule(sys, env, r)?);
+            }
+            Derivation::new(judge(expr, &evaled), rule, premises)
+        }
+        Expr::Match(e0, clauses) => {
+            if let EvalResult::Err(_) = evaled {
+                return Err(anyhow::anyhow!("{:?} has no error rule for match: {}", sys, expr))
+            }
+            // EvalML4 の match は [] -> e1 | x :: y -> e2 の形だけ
+            let ml4 = matches!(clauses.as_slice(), [(Pattern::Nil, _), (Pattern::Cons(x, y), _)]
+                if matches!((x.as_ref(), y.as_ref()), (Pattern::Var(_), Pattern::Var(_))));
+            let matched = eval(env, e0);
+            let (rule, premises) = match (sys, &matched, clauses.as_slice()) {
+                (System::EvalML5, EvalResult::Value(v), [(p, e), rest @ ..]) => {
+                    let mut premises = vec![apply_rule(sys, env, e0)?];
+                    premises.extend(apply_match(p, v));
+                    match pattern_match(p, v) {
+                        Some(new_env) => {
+                            premises.push(apply_rule(sys, &new_env.appended(env), e)?);
+                            (if rest.is_empty() { "E-MatchM1" } else { "E-MatchM2" }, premises)
+                        }
+                        None => {
+                            premises.push(apply_rule(sys, env, &Expr::Match(e0.clone(), rest.to_vec()))?);
+                            ("E-MatchN", premises)
+                        }
+                    }
+                }
+                (_, EvalResult::Value(Value::Nil), [(_, e1), _]) if ml4 =>
+                    ("E-MatchNil", vec![apply_rule(sys, env, e0)?, apply_rule(sys, env, e1)?]),
+                (_, EvalResult::Value(v @ Value::Cons(_, _)), [_, (p, e2)]) if ml4 => {
+                    let mut premises = vec![apply_rule(sys, env, e0)?];
+                    if let Some(new_env) = pattern_match(p, v) {
+                        premises.push(apply_rule(sys, &new_env.appended(env), e2)?);
+                    }
+                    ("E-MatchCons", premises)
+                }
+                _ => return Err(anyhow::anyhow!("EvalML4 match must be [] -> e | x :: y -> e: {}", expr))
+            };
+            Derivation::new(judge(expr, &evaled), rule, premises)
+        }
+        Expr::Index(_) | Expr::NamelessLet(_, _) | Expr::NamelessFun(_) | Expr::NamelessLetRec(_, _) =>
+            unreachable!("internal: nameless expressions are not evaluated by apply_rule"),
+        Expr::Hole | Expr::LetCc(_, _) => unreachable!("internal: holes and letcc appear only in EvalContML"),
+        Expr::Ref(_) | Expr::Deref(_) | Expr::Assign(_, _) => unreachable!("internal: references appear only in EvalRefML3")
+    };
+    Ok(d)
+}
+
+// p matches v when (ε) / p doesn't match v
+// リスト以外の値に対する [] や :: のパターンには規則がないので None
+fn apply_match<'a>(p: &Pattern<'a>, v: &Value<'a>) -> Option<Derivation> {
+    let d = match (p, v) {
+        (Pattern::Var(x), v) => Derivation::new(format!("{} matches {} when ({} = {})", p, v, x, v), "M-Var", vec![]),
+        (Pattern::Wild, v) => Derivation::new(format!("_ matches {} when ()", v), "M-Wild", vec![]),
+        (Pattern::Nil, Value::Nil) => Derivation::new("[] matches [] when ()".to_string(), "M-Nil", vec![]),
+        (Pattern::Nil, Value::Cons(_, _)) => Derivation::new(format!("[] doesn't match {}", v), "NM-NilCons", vec![]),
+        (Pattern::Cons(_, _), Value::Nil) => Derivation::new(format!("{} doesn't match []", p), "NM-ConsNil", vec![]),
+        (Pattern::Cons(p1, p2), Value::Cons(v1, v2)) => {
+            match (pattern_match(p1, v1), pattern_match(p2, v2)) {
+                (Some(env1), Some(env2)) => {
+                    let premises = apply_match(p1, v1).into_iter().chain(apply_match(p2, v2)).collect();
+                    Derivation::new(format!("{} matches {} when ({})", p, v, env2.appended(&env1)), "M-Cons", premises)
+                }
+                (None, _) => Derivation::new(format!("{} doesn't match {}", p, v), "NM-ConsConsL", apply_match(p1, v1).into_iter().collect()),
+                (_, None) => Derivation::new(format!("{} doesn't match {}", p, v), "NM-ConsConsR", apply_match(p2, v2).into_iter().collect())
+            }
+        }
+        _ => return None
+    };
+    Some(d)
 }
 
-fn get_env_var<'a>(env: &'a Env, name: &'a String) -> Option<&'a Value> {
+fn get_env_var<'a, 'b>(env: &'b Env<'a>, name: &String) -> Option<&'b Value<'a>> {
     for e in &env.0 {
         if e.0 == name {
             if let Expr::Value(x) = e.1.as_ref() {
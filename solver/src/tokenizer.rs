@@ -13,7 +13,29 @@ pub enum Operator {
 pub enum Sym {
     LParen,
     RParen,
-    Comma
+    LBracket,
+    RBracket,
+    Comma,
+    Arrow,
+    Cons,
+    Bar,
+    Dot,
+    LBrace,
+    RBrace,
+    // |-
+    Turnstile,
+    // >>
+    ContArrow,
+    // =>
+    RetArrow,
+    // EvalRefML3 のストアの区切り /
+    Slash,
+    // !
+    Bang,
+    // :=
+    Assign,
+    // TypingML4 の型注釈 :
+    Colon
 }
 
 #[derive(Debug, PartialEq)]
@@ -27,32 +49,47 @@ pub enum Token {
     Else,
     Var(String),
     Let,
-    In
+    In,
+    Fun,
+    Rec,
+    Match,
+    With,
+    // #n
+    Index(usize),
+    LetCc,
+    Ref,
+    // @l
+    Loc(String),
+    // 型変数 'a
+    TyVar(String)
 }
 
-pub fn tokenize<'a>(chars: &'a [u8]) -> anyhow::Result<Vec<Token>> {
+pub fn tokenize(chars: &[u8]) -> anyhow::Result<Vec<Token>> {
     match chars {
-        [b'1'..=b'9', ..] => {
-            let (num, rest) = get_num(chars);
+        [b'0'..=b'9', ..] => {
+            let (num, rest) = get_num(chars)?;
             Ok(new_token(Token::Int(num), rest)?)
         }
-        [b'i', b'f', rest @ ..] => {
+        [b'i', b'f', rest @ ..] if !continues_var(rest) => {
             Ok(new_token(Token::If, rest)?)
         }
-        [b't', b'h', b'e', b'n', rest @ ..] => {
+        [b't', b'h', b'e', b'n', rest @ ..] if !continues_var(rest) => {
             Ok(new_token(Token::Then, rest)?)
         }
-        [b'e', b'l', b's', b'e', rest @ ..] => {
+        [b'e', b'l', b's', b'e', rest @ ..] if !continues_var(rest) => {
             Ok(new_token(Token::Else, rest)?) }
-        [b't', b'r', b'u', b'e', rest @ ..] => {
+        [b't', b'r', b'u', b'e', rest @ ..] if !continues_var(rest) => {
             Ok(new_token(Token::Bool(true), rest)?)
         }
-        [b'f', b'a', b'l', b's', b'e', rest @ ..] => {
+        [b'f', b'a', b'l', b's', b'e', rest @ ..] if !continues_var(rest) => {
             Ok(new_token(Token::Bool(false), rest)?)
         }
         [b'+', rest @ ..] => {
             Ok(new_token(Token::Op(Operator::Plus), rest)?)
         }
+        [b'-', b'>', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::Arrow), rest)?)
+        }
         [b'-', rest @ ..] => {
             Ok(new_token(Token::Op(Operator::Minus), rest)?)
         }
@@ -68,18 +105,90 @@ pub fn tokenize<'a>(chars: &'a [u8]) -> anyhow::Result<Vec<Token>> {
         [b')', rest @ ..] => {
             Ok(new_token(Token::Sym(Sym::RParen), rest)?)
         }
+        [b'[', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::LBracket), rest)?)
+        }
+        [b']', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::RBracket), rest)?)
+        }
+        [b'=', b'>', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::RetArrow), rest)?)
+        }
+        [b'>', b'>', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::ContArrow), rest)?)
+        }
+        [b'{', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::LBrace), rest)?)
+        }
+        [b'}', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::RBrace), rest)?)
+        }
         [b'=', rest @ ..] => {
             Ok(new_token(Token::Op(Operator::Equal), rest)?)
         }
         [b',', rest @ ..] => {
             Ok(new_token(Token::Sym(Sym::Comma), rest)?)
         }
-        [b'l', b'e', b't', rest @ ..] => {
+        [b':', b'=', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::Assign), rest)?)
+        }
+        [b'/', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::Slash), rest)?)
+        }
+        [b'!', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::Bang), rest)?)
+        }
+        [b'\'', rest @ ..] => {
+            let (a, rest) = get_var(rest);
+            Ok(new_token(Token::TyVar(a), rest)?)
+        }
+        [b'@', rest @ ..] => {
+            let (l, rest) = get_var(rest);
+            Ok(new_token(Token::Loc(l), rest)?)
+        }
+        [b':', b':', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::Cons), rest)?)
+        }
+        [b':', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::Colon), rest)?)
+        }
+        [b'|', b'-', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::Turnstile), rest)?)
+        }
+        [b'|', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::Bar), rest)?)
+        }
+        [b'.', rest @ ..] => {
+            Ok(new_token(Token::Sym(Sym::Dot), rest)?)
+        }
+        [b'#', rest @ ..] => {
+            let (num, rest) = get_num(rest)?;
+            Ok(new_token(Token::Index(num as usize), rest)?)
+        }
+        [b'l', b'e', b't', b'c', b'c', rest @ ..] if !continues_var(rest) => {
+            Ok(new_token(Token::LetCc, rest)?)
+        }
+        [b'l', b'e', b't', rest @ ..] if !continues_var(rest) => {
             Ok(new_token(Token::Let, rest)?)
         }
-        [b'i', b'n', rest @ ..] => {
+        [b'i', b'n', rest @ ..] if !continues_var(rest) => {
             Ok(new_token(Token::In, rest)?)
         }
+        [b'f', b'u', b'n', rest @ ..] if !continues_var(rest) => {
+            Ok(new_token(Token::Fun, rest)?)
+        }
+        [b'r', b'e', b'c', rest @ ..] if !continues_var(rest) => {
+            Ok(new_token(Token::Rec, rest)?)
+        }
+        [b'r', b'e', b'f', rest @ ..] if !continues_var(rest) => {
+            Ok(new_token(Token::Ref, rest)?)
+        }
+        [b'm', b'a', b't', b'c', b'h', rest @ ..] if !continues_var(rest) => {
+            Ok(new_token(Token::Match, rest)?)
+        }
+        [b'w', b'i', b't', b'h', rest @ ..] if !continues_var(rest) => {
+            Ok(new_token(Token::With, rest)?)
+        }
         [b'_' | b'a'..=b'z', ..] => {
             let (var, rest) = get_var(chars);
             Ok(new_token(Token::Var(var), rest)?)
@@ -98,15 +207,18 @@ fn new_token(token: Token, chars: &[u8]) -> anyhow::Result<Vec<Token>> {
     Ok(tokens)
 }
 
-fn get_num(chars: &[u8]) -> (isize, &[u8]) {
+// キーワードの直後に識別子の文字が続くときは識別子として読む (refx など)
+fn continues_var(chars: &[u8]) -> bool {
+    matches!(chars, [b'_' | b'a'..=b'z' | b'A'..=b'Z' | b'\'', ..])
+}
+
+fn get_num(chars: &[u8]) -> anyhow::Result<(isize, &[u8])> {
     let (num_str, rest) = get_num_str(chars);
-    let num = str::from_utf8(&num_str)
-        .expect("invalid utf8 number")
-        .to_string()
-        .parse::<isize>()
-        .unwrap();
+    let num_str = str::from_utf8(&num_str)?;
+    let num = num_str.parse::<isize>()
+        .map_err(|_| anyhow::anyhow!("integer out of range: {}", num_str))?;
 
-    (num, rest)
+    Ok((num, rest))
 }
 
 fn get_var(chars: &[u8]) -> (String, &[u8]) {
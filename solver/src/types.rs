@@ -0,0 +1,120 @@
+use std::fmt;
+
+use crate::expr::Expr;
+
+// TypingML4, PolyTypingML4
+// x : int, f : 'a. 'a -> 'a |- e : t
+// 型を省略したときは推論した型を使う
+#[derive(Debug)]
+pub struct TypeForm<'a>(pub TypeEnv<'a>, pub Expr<'a>, pub Option<Type>);
+
+// τ ::= int | bool | τ -> τ | τ list | 'a
+// 型変数 'a, 'b, ... は番号で表す
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int,
+    Bool,
+    Fun(Box<Type>, Box<Type>),
+    List(Box<Type>),
+    // 型変数
+    // TypingML4 では推論の途中にだけ現れる
+    Var(usize)
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            // -> は右結合
+            Type::Fun(l, r) => match l.as_ref() {
+                Type::Fun(_, _) => write!(f, "({}) -> {}", l, r),
+                _ => write!(f, "{} -> {}", l, r)
+            },
+            // list は -> より強く結合する
+            Type::List(t) => match t.as_ref() {
+                Type::Fun(_, _) => write!(f, "({}) list", t),
+                _ => write!(f, "{} list", t)
+            },
+            Type::Var(n) => write!(f, "'{}", var_name(*n))
+        }
+    }
+}
+
+// 'a, 'b, ..., 'z, 'a1, ...
+fn var_name(n: usize) -> String {
+    let c = (b'a' + (n % 26) as u8) as char;
+    match n / 26 {
+        0 => c.to_string(),
+        i => format!("{}{}", c, i)
+    }
+}
+
+// var_name の逆
+pub fn var_index(name: &str) -> Option<usize> {
+    match name.as_bytes() {
+        [c @ b'a'..=b'z'] => Some((c - b'a') as usize),
+        [c @ b'a'..=b'z', rest @ ..] => {
+            let i = std::str::from_utf8(rest).ok()?.parse::<usize>().ok()?;
+            Some(i * 26 + (c - b'a') as usize)
+        }
+        _ => None
+    }
+}
+
+// 型スキーム 'a 'b. τ
+// 量化する変数がなければただの型
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scheme(pub Vec<usize>, pub Type);
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "{}", self.1)
+        }
+        for (i, n) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", Type::Var(*n))?;
+        }
+        write!(f, ". {}", self.1)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeEnv<'a>(pub Vec<(&'a String, Scheme)>);
+
+// Env と同じく先頭が最後に追加された変数
+impl<'a> fmt::Display for TypeEnv<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (x, t)) in self.0.iter().enumerate().rev() {
+            write!(f, "{} : {}", x, t)?;
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> TypeEnv<'a> {
+    pub fn form(&self) -> String {
+        format!("{} |-", self)
+    }
+
+    pub fn pushed(&self, x: &'a String, t: Type) -> TypeEnv<'a> {
+        self.pushed_scheme(x, Scheme(vec![], t))
+    }
+
+    pub fn pushed_scheme(&self, x: &'a String, s: Scheme) -> TypeEnv<'a> {
+        let mut vars = vec![(x, s)];
+        vars.append(&mut self.0.clone());
+
+        TypeEnv(vars)
+    }
+
+    pub fn get(&self, x: &String) -> Option<&Scheme> {
+        self.0.iter().find(|(name, _)| *name == x).map(|(_, t)| t)
+    }
+}
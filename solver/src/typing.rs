@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+
+use crate::expr::{Expr, Prim, Value, Pattern};
+use crate::types::{Type, Scheme, TypeEnv, TypeForm};
+use crate::solver::System;
+use crate::derivation::Derivation;
+
+// BNF
+// TypingML4
+// e ∈ Exp ::= i | b | x | e op e | if e then e else e | let x = e in e | fun x -> e | e e
+//             | let rec f = fun x -> e in e | [] | e :: e | match e with [] -> e | x :: y -> e
+// τ ∈ Types ::= int | bool | τ -> τ | τ list
+//
+// Γ |- e : τ
+//
+// PolyTypingML4
+// τ ∈ Types ::= ... | 'a
+// σ ∈ TyScheme ::= τ | 'a1 ... 'an. τ
+//
+// Γ |- e : τ
+// Γ の変数には型スキームが付き, T-Var で具体化し, T-Let と T-LetRec で一般化する
+//
+// 型変数を使って単一化で推論する
+// TypingML4 では最後まで決まらなかった型変数は int にし, PolyTypingML4 では型変数のまま残す
+// 導出の各段では環境と型が決まっているので, その段の前提の型だけを改めて推論する
+
+pub fn solve(form: &TypeForm, sys: System) -> anyhow::Result<()> {
+    print!("{}", derive(form, sys)?);
+    Ok(())
+}
+
+pub fn derive(form: &TypeForm, sys: System) -> anyhow::Result<Derivation> {
+    let TypeForm(env, expr, expected) = form;
+    let mut inf = Infer::new(sys, env, expected.as_ref().unwrap_or(&Type::Int));
+    let t = inf.infer(env, expr)?;
+    if let Some(expected) = expected {
+        inf.unify(&t, expected)?;
+    }
+    let t = inf.close(&t);
+    // 入力に書かれた型変数の名前はそのまま使う
+    let mut vars = vec![];
+    env_vars(env, &mut vars);
+    if let Some(expected) = expected {
+        type_vars(expected, &mut vars);
+    }
+    let mut names = Names(vars.into_iter().map(|n| (n, n)).collect());
+    Ok(apply_rule(sys, &mut names, env, expr, &t))
+}
+
+// 導出に出てくる型変数を 'a, 'b, ... から順に名前を付け直す
+// 番号が違えば名前も違うので, 一つの判断の中で別の型変数が同じ名前になることはない
+struct Names(HashMap<usize, usize>);
+
+impl Names {
+    fn rename(&mut self, n: usize) -> usize {
+        if let Some(m) = self.0.get(&n) {
+            return *m
+        }
+        let m = (0..).find(|m| !self.0.values().any(|v| v == m)).unwrap();
+        self.0.insert(n, m);
+        m
+    }
+
+    fn ty(&mut self, t: &Type) -> Type {
+        match t {
+            Type::Var(n) => Type::Var(self.rename(*n)),
+            Type::Fun(l, r) => Type::Fun(Box::new(self.ty(l)), Box::new(self.ty(r))),
+            Type::List(t) => Type::List(Box::new(self.ty(t))),
+            _ => t.clone()
+        }
+    }
+
+    fn env<'a>(&mut self, env: &TypeEnv<'a>) -> TypeEnv<'a> {
+        // 古い変数から名前を付ける
+        let vars = env.0.iter().rev()
+            .map(|(x, Scheme(bound, t))| (*x, Scheme(bound.iter().map(|n| self.rename(*n)).collect(), self.ty(t))))
+            .collect::<Vec<_>>();
+
+        TypeEnv(vars.into_iter().rev().collect())
+    }
+}
+
+struct Infer {
+    poly: bool,
+    next: usize,
+    subst: HashMap<usize, Type>
+}
+
+impl Infer {
+    // 環境と結論の型に出てくる型変数とは別の番号から新しい型変数を作る
+    fn new(sys: System, env: &TypeEnv, t: &Type) -> Infer {
+        let mut vars = vec![];
+        env_vars(env, &mut vars);
+        type_vars(t, &mut vars);
+        let next = vars.iter().max().map_or(0, |n| n + 1);
+
+        Infer { poly: sys == System::PolyTypingML4, next, subst: HashMap::new() }
+    }
+
+    fn fresh(&mut self) -> Type {
+        self.next += 1;
+        Type::Var(self.next - 1)
+    }
+
+    // 代入を最後まで適用した型
+    fn resolve(&self, t: &Type) -> Type {
+        match t {
+            Type::Var(n) => match self.subst.get(n) {
+                Some(t) => self.resolve(t),
+                None => t.clone()
+            },
+            Type::Fun(l, r) => Type::Fun(Box::new(self.resolve(l)), Box::new(self.resolve(r))),
+            Type::List(t) => Type::List(Box::new(self.resolve(t))),
+            _ => t.clone()
+        }
+    }
+
+    // 決まらなかった型変数は int にする
+    fn ground(&self, t: &Type) -> Type {
+        match self.resolve(t) {
+            Type::Var(_) => Type::Int,
+            Type::Fun(l, r) => Type::Fun(Box::new(self.ground(&l)), Box::new(self.ground(&r))),
+            Type::List(t) => Type::List(Box::new(self.ground(&t))),
+            t => t
+        }
+    }
+
+    // TypingML4 では型変数を残さない
+    fn close(&self, t: &Type) -> Type {
+        if self.poly {
+            self.resolve(t)
+        } else {
+            self.ground(t)
+        }
+    }
+
+    // 'a1 ... 'an. τ の 'ai を新しい型変数に置き換える
+    fn instantiate(&mut self, s: &Scheme) -> Type {
+        let Scheme(bound, t) = s;
+        let fresh = bound.iter().map(|n| (*n, self.fresh())).collect::<HashMap<_, _>>();
+        substitute(t, &fresh)
+    }
+
+    // Γ に自由に出てこない型変数を量化する
+    // TypingML4 では一般化しない
+    fn generalize(&self, env: &TypeEnv, t: &Type) -> Scheme {
+        let t = self.resolve(t);
+        if !self.poly {
+            return Scheme(vec![], t)
+        }
+        let mut env_vars = vec![];
+        for (_, s) in &env.0 {
+            let mut vars = vec![];
+            type_vars(&self.resolve(&s.1), &mut vars);
+            env_vars.extend(vars.into_iter().filter(|n| !s.0.contains(n)));
+        }
+        let mut vars = vec![];
+        type_vars(&t, &mut vars);
+        vars.retain(|n| !env_vars.contains(n));
+
+        Scheme(vars, t)
+    }
+
+    fn unify(&mut self, t1: &Type, t2: &Type) -> anyhow::Result<()> {
+        match (self.resolve(t1), self.resolve(t2)) {
+            (Type::Var(a), Type::Var(b)) if a == b => Ok(()),
+            // 新しい型変数のほうを置き換えて, 環境や結論に出てくる型変数を残す
+            (Type::Var(a), Type::Var(b)) => {
+                self.subst.insert(a.max(b), Type::Var(a.min(b)));
+                Ok(())
+            }
+            (Type::Var(a), t) | (t, Type::Var(a)) => {
+                if occurs(a, &t) {
+                    return Err(anyhow::anyhow!("type error: {} occurs in {}", Type::Var(a), t))
+                }
+                self.subst.insert(a, t);
+                Ok(())
+            }
+            (Type::Int, Type::Int) | (Type::Bool, Type::Bool) => Ok(()),
+            (Type::Fun(l1, r1), Type::Fun(l2, r2)) => {
+                self.unify(&l1, &l2)?;
+                self.unify(&r1, &r2)
+            }
+            (Type::List(t1), Type::List(t2)) => self.unify(&t1, &t2),
+            (t1, t2) => Err(anyhow::anyhow!("type error: {} and {} do not match", t1, t2))
+        }
+    }
+
+    fn infer<'a>(&mut self, env: &TypeEnv<'a>, expr: &Expr<'a>) -> anyhow::Result<Type> {
+        match expr {
+            Expr::Value(Value::Int(_)) => Ok(Type::Int),
+            Expr::Value(Value::Bool(_)) => Ok(Type::Bool),
+            Expr::Value(Value::Nil) => Ok(Type::List(Box::new(self.fresh()))),
+            Expr::Prim(p) => {
+                let (l, r) = operands(p);
+                let t1 = self.infer(env, l)?;
+                self.unify(&t1, &Type::Int)?;
+                let t2 = self.infer(env, r)?;
+                self.unify(&t2, &Type::Int)?;
+                match p {
+                    Prim::LessThan(_, _) => Ok(Type::Bool),
+                    _ => Ok(Type::Int)
+                }
+            }
+            Expr::IfThenElse(cond, then, els) => {
+                let t1 = self.infer(env, cond)?;
+                self.unify(&t1, &Type::Bool)?;
+                let t2 = self.infer(env, then)?;
+                let t3 = self.infer(env, els)?;
+                self.unify(&t2, &t3)?;
+                Ok(t2)
+            }
+            Expr::Ident(x) => {
+                let s = env.get(x).cloned().ok_or_else(|| anyhow::anyhow!("unbound variable: {}", x))?;
+                Ok(self.instantiate(&s))
+            }
+            Expr::Let(x, e1, e2) => {
+                let t1 = self.infer(env, e1)?;
+                let s = self.generalize(env, &t1);
+                self.infer(&env.pushed_scheme(x, s), e2)
+            }
+            Expr::Fun(x, body) => {
+                let t1 = self.fresh();
+                let t2 = self.infer(&env.pushed(x, t1.clone()), body)?;
+                Ok(Type::Fun(Box::new(t1), Box::new(t2)))
+            }
+            Expr::App(fun, arg) => {
+                let t1 = self.infer(env, fun)?;
+                let t2 = self.infer(env, arg)?;
+                let t = self.fresh();
+                self.unify(&t1, &Type::Fun(Box::new(t2), Box::new(t.clone())))?;
+                Ok(t)
+            }
+            Expr::LetRec(f, x, e1, e2) => {
+                let (t1, t2) = (self.fresh(), self.fresh());
+                let fun_t = Type::Fun(Box::new(t1.clone()), Box::new(t2.clone()));
+                let body_t = self.infer(&env.pushed(f, fun_t.clone()).pushed(x, t1), e1)?;
+                self.unify(&body_t, &t2)?;
+                let s = self.generalize(env, &fun_t);
+                self.infer(&env.pushed_scheme(f, s), e2)
+            }
+            Expr::Cons(e1, e2) => {
+                let t1 = self.infer(env, e1)?;
+                let t2 = self.infer(env, e2)?;
+                self.unify(&t2, &Type::List(Box::new(t1)))?;
+                Ok(t2)
+            }
+            Expr::Match(e0, clauses) => {
+                let (e1, x, y, e2) = match_clauses(clauses)
+                    .ok_or_else(|| anyhow::anyhow!("TypingML4 supports only match e with [] -> e | x :: y -> e"))?;
+                let elem = self.fresh();
+                let t0 = self.infer(env, e0)?;
+                self.unify(&t0, &Type::List(Box::new(elem.clone())))?;
+                let t1 = self.infer(env, e1)?;
+                let t2 = self.infer(&env.pushed(x, elem.clone()).pushed(y, Type::List(Box::new(elem))), e2)?;
+                self.unify(&t1, &t2)?;
+                Ok(t1)
+            }
+            _ => Err(anyhow::anyhow!("TypingML4 does not support: {}", expr))
+        }
+    }
+}
+
+// 量化された型変数も含めて集める
+fn env_vars(env: &TypeEnv, vars: &mut Vec<usize>) {
+    for (_, Scheme(bound, t)) in env.0.iter().rev() {
+        for n in bound {
+            if !vars.contains(n) {
+                vars.push(*n);
+            }
+        }
+        type_vars(t, vars);
+    }
+}
+
+// 出てくる順に重複なく集める
+pub fn type_vars(t: &Type, vars: &mut Vec<usize>) {
+    match t {
+        Type::Var(n) if !vars.contains(n) => vars.push(*n),
+        Type::Fun(l, r) => {
+            type_vars(l, vars);
+            type_vars(r, vars);
+        }
+        Type::List(t) => type_vars(t, vars),
+        _ => {}
+    }
+}
+
+pub fn substitute(t: &Type, s: &HashMap<usize, Type>) -> Type {
+    match t {
+        Type::Var(n) => s.get(n).cloned().unwrap_or_else(|| t.clone()),
+        Type::Fun(l, r) => Type::Fun(Box::new(substitute(l, s)), Box::new(substitute(r, s))),
+        Type::List(t) => Type::List(Box::new(substitute(t, s))),
+        _ => t.clone()
+    }
+}
+
+fn occurs(a: usize, t: &Type) -> bool {
+    match t {
+        Type::Var(b) => a == *b,
+        Type::Fun(l, r) => occurs(a, l) || occurs(a, r),
+        Type::List(t) => occurs(a, t),
+        _ => false
+    }
+}
+
+fn operands<'a, 'b>(p: &'b Prim<'a>) -> (&'b Expr<'a>, &'b Expr<'a>) {
+    match p {
+        Prim::Add(l, r) | Prim::Sub(l, r) | Prim::Mul(l, r) | Prim::LessThan(l, r) => (l, r)
+    }
+}
+
+// [] -> e1 | x :: y -> e2
+pub fn match_clauses<'a, 'b>(clauses: &'b [(Pattern<'a>, Expr<'a>)]) -> Option<(&'b Expr<'a>, &'a String, &'a String, &'b Expr<'a>)> {
+    match clauses {
+        [(Pattern::Nil, e1), (Pattern::Cons(x, y), e2)] => match (x.as_ref(), y.as_ref()) {
+            (Pattern::Var(x), Pattern::Var(y)) => Some((e1, x, y, e2)),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+// 推論が成功した判断に対してだけ呼ぶ
+// env と t に出てくる型変数は具体化しない
+// 型変数の名前は出てくる順に付けるので, 結論を先に, 前提を左から順に組み立てる
+fn apply_rule<'a>(sys: System, names: &mut Names, env: &TypeEnv<'a>, expr: &Expr<'a>, t: &Type) -> Derivation {
+    let judge = format!("{} {} : {}", names.env(env).form(), expr, names.ty(t));
+    // 前提の型は結論の型を与えてもう一度推論して決める
+    let mut inf = Infer::new(sys, env, t);
+    let t0 = inf.infer(env, expr).expect("internal: infer failed on apply_rule");
+    inf.unify(&t0, t).expect("internal: unify failed on apply_rule");
+    match expr {
+        Expr::Value(Value::Int(_)) => Derivation::new(judge, "T-Int", vec![]),
+        Expr::Value(Value::Bool(_)) => Derivation::new(judge, "T-Bool", vec![]),
+        Expr::Value(Value::Nil) => Derivation::new(judge, "T-Nil", vec![]),
+        Expr::Prim(p) => {
+            let rule = match p {
+                Prim::Add(_, _) => "T-Plus",
+                Prim::Sub(_, _) => "T-Minus",
+                Prim::Mul(_, _) => "T-Times",
+                Prim::LessThan(_, _) => "T-Lt"
+            };
+            let (l, r) = operands(p);
+            let premises = vec![apply_rule(sys, names, env, l, &Type::Int), apply_rule(sys, names, env, r, &Type::Int)];
+            Derivation::new(judge, rule, premises)
+        }
+        Expr::IfThenElse(cond, then, els) => {
+            let premises = vec![
+                apply_rule(sys, names, env, cond, &Type::Bool),
+                apply_rule(sys, names, env, then, t),
+                apply_rule(sys, names, env, els, t)
+            ];
+            Derivation::new(judge, "T-If", premises)
+        }
+        Expr::Ident(_) => Derivation::new(judge, "T-Var", vec![]),
+        Expr::Let(x, e1, e2) => {
+            let t1 = inf.infer(env, e1).unwrap();
+            let s = inf.generalize(env, &t1);
+            let t2 = inf.infer(&env.pushed_scheme(x, s), e2).unwrap();
+            inf.unify(&t2, t).unwrap();
+            let t1 = inf.close(&t1);
+            let s = inf.generalize(env, &t1);
+            let premises = vec![apply_rule(sys, names, env, e1, &t1), apply_rule(sys, names, &env.pushed_scheme(x, s), e2, t)];
+            Derivation::new(judge, "T-Let", premises)
+        }
+        Expr::Fun(x, body) => {
+            let (t1, t2) = match t {
+                Type::Fun(t1, t2) => (t1.as_ref().clone(), t2.as_ref()),
+                _ => unreachable!("internal: function type expected on T-Fun")
+            };
+            Derivation::new(judge, "T-Fun", vec![apply_rule(sys, names, &env.pushed(x, t1), body, t2)])
+        }
+        Expr::App(fun, arg) => {
+            let t1 = inf.infer(env, fun).unwrap();
+            let t2 = inf.infer(env, arg).unwrap();
+            inf.unify(&t1, &Type::Fun(Box::new(t2.clone()), Box::new(t.clone()))).unwrap();
+            let t2 = inf.close(&t2);
+            let premises = vec![
+                apply_rule(sys, names, env, fun, &Type::Fun(Box::new(t2.clone()), Box::new(t.clone()))),
+                apply_rule(sys, names, env, arg, &t2)
+            ];
+            Derivation::new(judge, "T-App", premises)
+        }
+        Expr::LetRec(f, x, e1, e2) => {
+            let (t1, t2) = (inf.fresh(), inf.fresh());
+            let fun_t = Type::Fun(Box::new(t1.clone()), Box::new(t2.clone()));
+            let body_t = inf.infer(&env.pushed(f, fun_t.clone()).pushed(x, t1.clone()), e1).unwrap();
+            inf.unify(&body_t, &t2).unwrap();
+            let s = inf.generalize(env, &fun_t);
+            let t3 = inf.infer(&env.pushed_scheme(f, s), e2).unwrap();
+            inf.unify(&t3, t).unwrap();
+            let (t1, t2, fun_t) = (inf.close(&t1), inf.close(&t2), inf.close(&fun_t));
+            let s = inf.generalize(env, &fun_t);
+            let premises = vec![
+                apply_rule(sys, names, &env.pushed(f, fun_t).pushed(x, t1), e1, &t2),
+                apply_rule(sys, names, &env.pushed_scheme(f, s), e2, t)
+            ];
+            Derivation::new(judge, "T-LetRec", premises)
+        }
+        Expr::Cons(e1, e2) => {
+            let elem = match t {
+                Type::List(elem) => elem.as_ref(),
+                _ => unreachable!("internal: list type expected on T-Cons")
+            };
+            Derivation::new(judge, "T-Cons", vec![apply_rule(sys, names, env, e1, elem), apply_rule(sys, names, env, e2, t)])
+        }
+        Expr::Match(e0, clauses) => {
+            let (e1, x, y, e2) = match_clauses(clauses).unwrap();
+            let t0 = inf.infer(env, e0).unwrap();
+            let elem = inf.fresh();
+            inf.unify(&t0, &Type::List(Box::new(elem.clone()))).unwrap();
+            // e1, e2 の推論で要素の型が決まることもある
+            let t1 = inf.infer(env, e1).unwrap();
+            inf.unify(&t1, t).unwrap();
+            let t2 = inf.infer(&env.pushed(x, elem.clone()).pushed(y, Type::List(Box::new(elem.clone()))), e2).unwrap();
+            inf.unify(&t2, t).unwrap();
+            let elem = inf.close(&elem);
+            let premises = vec![
+                apply_rule(sys, names, env, e0, &Type::List(Box::new(elem.clone()))),
+                apply_rule(sys, names, env, e1, t),
+                apply_rule(sys, names, &env.pushed(x, elem.clone()).pushed(y, Type::List(Box::new(elem))), e2, t)
+            ];
+            Derivation::new(judge, "T-Match", premises)
+        }
+        _ => unreachable!("internal: unsupported expression on apply_rule")
+    }
+}
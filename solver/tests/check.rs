@@ -0,0 +1,217 @@
+use std::fs;
+use std::path::Path;
+
+use solver::check::{check, CheckError};
+use solver::derivation::parse_derivation;
+use solver::diff::derive_reference;
+use solver::parser::parse;
+use solver::render::{render, Format};
+use solver::solver::{derive, System};
+use solver::tokenizer::tokenize;
+
+mod common;
+use common::exercises;
+
+// solver の導出を CoPL の形で書き, 読み直して検査する
+fn solve_and_check(sys: System, env: &str, expr: &str) {
+    let env = tokenize(env.as_bytes()).unwrap();
+    let expr = tokenize(expr.as_bytes()).unwrap();
+    let d = derive(&parse(&env, &expr).unwrap(), sys).unwrap();
+    let text = render(&d, sys, &"copl".parse::<Format>().unwrap());
+    let back = parse_derivation(&text).unwrap();
+    check(&back, sys).unwrap_or_else(|e| panic!("{}\n{}", text, e));
+}
+
+#[test]
+fn check_solver_derivations() {
+    for (env, expr) in [
+        ("", "3 + 5 * 2 - 1 < 12"),
+        ("x = 3, y = 2", "if x < y then x else y * 2"),
+        ("", "let x = 1 + 2 in let y = x * 2 in x + y"),
+        ("", "let f = fun x -> x + 1 in f 2"),
+        ("", "let rec fact = fun n -> if n < 2 then 1 else n * fact (n - 1) in fact 3")
+    ] {
+        solve_and_check(System::EvalML3, env, expr);
+    }
+}
+
+#[test]
+fn check_solver_error_derivations() {
+    for (env, expr) in [
+        ("", "true + 1"),
+        ("", "1 - (2 < 3)"),
+        ("", "if 1 then 2 else 3"),
+        ("", "if 1 + true then 1 else 2"),
+        ("", "if false then 1 else true * 1"),
+        ("x = 1", "y"),
+        ("", "let x = true + 1 in x"),
+        ("", "let x = 1 in x + true")
+    ] {
+        solve_and_check(System::EvalML3, env, expr);
+    }
+}
+
+// 関数適用と let rec の誤り, EvalML4 と EvalML5 の誤りには CoPL の規則がないので導出しない
+#[test]
+fn reject_errors_without_rules() {
+    for (sys, expr, message) in [
+        (System::EvalML3, "let f = fun x -> x + true in f 1", "EvalML3 has no error rule"),
+        (System::EvalML3, "let f = fun x -> x in f (1 + true)", "EvalML3 has no error rule"),
+        (System::EvalML3, "(1 + true) 2", "EvalML3 has no error rule"),
+        (System::EvalML3, "1 2", "EvalML3 has no error rule"),
+        (System::EvalML3, "let rec f = fun x -> x in f true + 1", "EvalML3 has no error rule"),
+        (System::EvalML4, "1 :: true + 2 :: []", "EvalML4 has no error rules"),
+        (System::EvalML4, "match 1 with [] -> 0 | x :: y -> x", "EvalML4 has no error rules"),
+        (System::EvalML4, "match [] with x :: y -> 1 | [] -> 0", "EvalML4 match must be [] -> e | x :: y -> e"),
+        (System::EvalML4, "match 1 :: [] with [] -> 0 | _ :: y -> 1", "EvalML4 match must be [] -> e | x :: y -> e"),
+        (System::EvalML5, "match 1 :: [] with [] -> 0", "EvalML5 has no error rules")
+    ] {
+        let tokens = tokenize(expr.as_bytes()).unwrap();
+        let e = derive(&parse(&[], &tokens).unwrap(), sys).unwrap_err();
+        assert!(e.to_string().starts_with(message), "{}: {}", expr, e);
+    }
+}
+
+fn check_file(path: &Path, sys: System) -> Result<(), CheckError> {
+    check(&parse_derivation(&fs::read_to_string(path).unwrap()).unwrap(), sys)
+}
+
+#[test]
+fn check_exercises() {
+    let games = [
+        ("nat", System::Nat),
+        ("eval-nat-exp", System::EvalNatExp),
+        ("reduce-nat-exp", System::ReduceNatExp),
+        ("eval-ml1", System::EvalML1),
+        ("eval-ml2", System::EvalML3),
+        ("eval-ml3", System::EvalML3)
+    ];
+    for (dir, sys) in games {
+        for path in exercises(dir) {
+            // eval-ml2/q038 は間違った答え (check_wrong_exercise), nat/q004 は閉じ括弧が多い (malformed_exercise)
+            if path.ends_with("eval-ml2/q038.txt") || path.ends_with("nat/q004.txt") {
+                continue
+            }
+            check_file(&path, sys).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        }
+    }
+    // compare-nat はどの CompareNat かがファイルごとに違う
+    for path in exercises("compare-nat") {
+        let ok = [System::CompareNat1, System::CompareNat2, System::CompareNat3].iter()
+            .any(|sys| check_file(&path, *sys).is_ok());
+        assert!(ok, "{}", path.display());
+    }
+}
+
+#[test]
+fn malformed_exercise() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../exercise/nat/q004.txt");
+    let e = parse_derivation(&fs::read_to_string(path).unwrap()).unwrap_err();
+    assert_eq!(e.to_string(), "line 6: unexpected '}' after derivation");
+}
+
+#[test]
+fn check_wrong_exercise() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../exercise/eval-ml2/q038.txt");
+    let e = check_file(&path, System::EvalML3).unwrap_err();
+    assert_eq!(e.path, vec![2]);
+    assert!(e.message.starts_with("premise 1: expected `x = 3, x = 6 |- x evalto ...`"), "{}", e);
+}
+
+fn check_error(text: &str, sys: System) -> CheckError {
+    check(&parse_derivation(text).unwrap(), sys).unwrap_err()
+}
+
+#[test]
+fn reject_wrong_rule() {
+    let e = check_error("S(Z) plus Z is S(Z) by P-Zero {}", System::Nat);
+    assert!(e.message.contains("P-Zero does not apply"), "{}", e);
+
+    let e = check_error("|- 1 + 2 evalto 3 by E-Times {
+        |- 1 evalto 1 by E-Int {};
+        |- 2 evalto 2 by E-Int {};
+        1 plus 2 is 3 by B-Plus {}
+    }", System::EvalML3);
+    assert_eq!(e.message, "E-Times does not apply to `1 + 2`; expected E-Plus");
+
+    let e = check_error("S(Z) is less than S(S(Z)) by L-Zero {}", System::CompareNat1);
+    assert!(e.message.starts_with("unknown rule L-Zero in CompareNat1"), "{}", e);
+
+    // 関数適用の誤りには規則がない
+    let e = check_error("|- 1 2 evalto error by E-AppError {
+        |- 1 evalto 1 by E-Int {};
+    }", System::EvalML3);
+    assert_eq!(e.message, "unknown rule E-AppError; no rule applies to `1 2`");
+
+    // - は整数の定数の前にしか書けないので, -(1 + 2) は E-Int で導けない
+    let e = check_error("|- -(1 + 2) evalto -3 by E-Int {};", System::EvalML3);
+    assert_eq!(e.message, "unary minus is only allowed before an integer literal");
+    check(&parse_derivation("|- -3 evalto -3 by E-Int {};").unwrap(), System::EvalML3).unwrap();
+}
+
+#[test]
+fn reject_wrong_premise_count() {
+    let e = check_error("S(Z) plus Z is S(Z) by P-Succ {}", System::Nat);
+    assert_eq!(e.message, "P-Succ takes 1 premise(s), found 0");
+
+    let e = check_error("|- 1 + 2 evalto 3 by E-Plus {
+        |- 1 evalto 1 by E-Int {};
+        1 plus 2 is 3 by B-Plus {}
+    }", System::EvalML3);
+    assert_eq!(e.message, "E-Plus takes 3 premise(s), found 2");
+}
+
+#[test]
+fn reject_wrong_value() {
+    let e = check_error("|- 1 + 2 evalto 4 by E-Plus {
+        |- 1 evalto 1 by E-Int {};
+        |- 2 evalto 2 by E-Int {};
+        1 plus 2 is 4 by B-Plus {}
+    }", System::EvalML3);
+    assert_eq!((e.path, e.message.as_str()), (vec![3], "1 plus 2 is 3, not 4"));
+
+    let e = check_error("S(Z) plus Z is S(S(Z)) by P-Succ {
+        Z plus Z is Z by P-Zero {}
+    }", System::Nat);
+    assert_eq!(e.message, "premise 1: expected `Z plus Z is S(Z)`, found `Z plus Z is Z`");
+
+    let e = check_error("x : int |- x + 1 : bool by T-Plus {
+        x : int |- x : int by T-Var {};
+        x : int |- 1 : int by T-Int {}
+    }", System::TypingML4);
+    assert_eq!(e.message, "T-Plus: conclusion type bool does not match result type int");
+}
+
+// 壊れた式は panic せずにエラーになる
+#[test]
+fn reject_malformed_expressions() {
+    let e = check_error("|- evalto 3 by E-Int {};", System::EvalML3);
+    assert!(e.message.contains("unexpected end of input"), "{}", e);
+
+    assert!(derive_reference("|- 3 + evalto 3", System::EvalML3).is_err());
+    for expr in ["let 3 = 1 in 2", "let x = 1 2", "99999999999999999999999 + 1"] {
+        let tokens = tokenize(expr.as_bytes());
+        assert!(tokens.and_then(|t| parse(&[], &t).map(|_| ())).is_err(), "{}", expr);
+    }
+}
+
+// EvalML1 には変数, let, 関数, 関数適用, let rec がない
+#[test]
+fn reject_ml3_syntax_in_ml1() {
+    for expr in ["x + 1", "let x = 1 in x", "fun x -> x", "(fun x -> x) 1", "let rec f = fun x -> x in f 1", "if true then 1 else let x = 2 in x"] {
+        let tokens = tokenize(expr.as_bytes()).unwrap();
+        let e = derive(&parse(&[], &tokens).unwrap(), System::EvalML1).unwrap_err();
+        assert!(e.to_string().starts_with("EvalML1 has no `"), "{}: {}", expr, e);
+    }
+    let env = tokenize("x = 1".as_bytes()).unwrap();
+    let expr = tokenize("1 + 2".as_bytes()).unwrap();
+    assert!(derive(&parse(&env, &expr).unwrap(), System::EvalML1).is_err());
+
+    let e = check_error("x = 1 |- x evalto 1 by E-Var1 {};", System::EvalML1);
+    assert_eq!(e.message, "EvalML1 has no environment; use EvalML2");
+    let e = check_error("let x = 1 in x evalto 1 by E-Let {
+        1 evalto 1 by E-Int {};
+        x = 1 |- x evalto 1 by E-Var1 {};
+    }", System::EvalML1);
+    assert_eq!(e.message, "EvalML1 has no `let x = 1 in x`; use EvalML2 or EvalML3");
+}
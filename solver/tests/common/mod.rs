@@ -0,0 +1,13 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// exercise/<dir> の答えのファイルを名前順に
+pub fn exercises(dir: &str) -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../exercise").join(dir);
+    let mut paths = fs::read_dir(root).unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "txt"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
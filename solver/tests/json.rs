@@ -0,0 +1,179 @@
+use std::fs;
+
+use solver::check::check;
+use solver::derivation::{parse_derivation, Derivation};
+use solver::json::{parse_json, Json};
+use solver::parser::{parse, parse_vars, parse_values, parse_cont, parse_cont_env, parse_store, parse_typing};
+use solver::schema::{export, import, names, expr_json, expr_from_json, value_from_json, VERSION};
+use solver::solver::System;
+use solver::tokenizer::tokenize;
+use solver::{nameless, cont, reference, typing};
+
+mod common;
+use common::exercises;
+
+fn derive(sys: System, env: &str, expr: &str) -> Derivation {
+    let env = tokenize(env.as_bytes()).unwrap();
+    let expr = tokenize(expr.as_bytes()).unwrap();
+    match sys {
+        System::NamelessML3 => nameless::derive(&parse_vars(&env, &expr).unwrap()).unwrap(),
+        System::EvalNamelessML3 => nameless::derive_eval(&parse_values(&env, &expr).unwrap()).unwrap(),
+        System::EvalContML1 => cont::derive(&parse_cont(&expr).unwrap()).unwrap(),
+        System::EvalContML4 => cont::derive(&parse_cont_env(&env, &expr).unwrap()).unwrap(),
+        System::EvalRefML3 => reference::derive(&parse_store(&env, &expr).unwrap()).unwrap(),
+        System::TypingML4 | System::PolyTypingML4 => typing::derive(&parse_typing(&env, &expr).unwrap(), sys).unwrap(),
+        _ => solver::solver::derive(&parse(&env, &expr).unwrap(), sys).unwrap()
+    }
+}
+
+// 書き出して読み直し, もう一度書き出したものが同じになる
+fn round_trip(d: &Derivation, sys: System) -> Derivation {
+    let text = export(d, sys).to_string();
+    let (back, back_sys) = import(&parse_json(&text).unwrap()).unwrap();
+    assert_eq!(back_sys, sys);
+    assert_eq!(export(&back, sys).to_string(), text);
+    back
+}
+
+// どの節にも form が付いている
+fn has_form(d: &Derivation, sys: System) -> bool {
+    fn every(node: &Json) -> bool {
+        node.get("form").is_some() && match node.get("premises") {
+            Some(Json::Array(ps)) => ps.iter().all(every),
+            _ => true
+        }
+    }
+    every(export(d, sys).get("derivation").unwrap())
+}
+
+#[test]
+fn round_trip_solver_derivations() {
+    let cases = [
+        (System::EvalML3, "x = 3", "let f = fun y -> x + y in f 2"),
+        (System::EvalML3, "", "let rec fact = fun n -> if n < 2 then 1 else n * fact (n - 1) in fact 3"),
+        (System::EvalML3, "", "if 2 + 3 then 1 else 3"),
+        (System::EvalML4, "", "let rec f = fun x -> if x < 1 then [] else x :: f (x - 1) in match f 2 with [] -> 0 | y :: z -> y"),
+        (System::EvalML5, "", "match 1 :: 2 :: [] with x :: _ -> x | [] -> 0"),
+        (System::EvalML5, "y = 3", "match 1 :: [] with [] -> 0 | x :: _ -> x + y"),
+        (System::NamelessML3, "x, y", "let z = x + y in fun w -> z w"),
+        (System::EvalNamelessML3, "1, true", "if #1 then #2 + 1 else 0"),
+        (System::EvalContML1, "", "3 >> {_ + 5} >> {_ * 2}"),
+        (System::EvalContML4, "", "1 + letcc k in 2 + k 3"),
+        (System::EvalContML4, "x = 2", "let f = fun y -> x * y in 1 + f 3 >> {_ + 4}"),
+        (System::EvalRefML3, "@l = 2 / x = @l", "x := !x + 1"),
+        (System::EvalRefML3, "", "let r = ref 1 in let s = ref !r in s := 3"),
+        (System::TypingML4, "x : int", "fun y -> x + y"),
+        (System::PolyTypingML4, "f : 'a. 'a -> 'a", "f f 3")
+    ];
+    for (sys, env, expr) in cases {
+        let d = derive(sys, env, expr);
+        let back = round_trip(&d, sys);
+        assert_eq!(back.judgment, d.judgment.trim(), "{:?}", sys);
+        assert!(has_form(&d, sys), "{:?}: {}", sys, d.judgment);
+        if matches!(sys, System::EvalML3 | System::TypingML4 | System::PolyTypingML4) {
+            check(&back, sys).unwrap();
+        }
+    }
+}
+
+#[test]
+fn round_trip_exercises() {
+    let games = [
+        ("nat", System::Nat),
+        ("compare-nat", System::CompareNat1),
+        ("eval-nat-exp", System::EvalNatExp),
+        ("reduce-nat-exp", System::ReduceNatExp),
+        ("eval-ml1", System::EvalML1),
+        ("eval-ml2", System::EvalML3),
+        ("eval-ml3", System::EvalML3)
+    ];
+    for (dir, sys) in games {
+        for path in exercises(dir) {
+            // nat/q004 は閉じ括弧が多くて読めない
+            if path.ends_with("nat/q004.txt") {
+                assert!(parse_derivation(&fs::read_to_string(&path).unwrap()).is_err());
+                continue
+            }
+            let d = parse_derivation(&fs::read_to_string(&path).unwrap()).unwrap();
+            let back = round_trip(&d, sys);
+            assert!(has_form(&d, sys), "{}", path.display());
+            // eval-ml2/q038 は間違った答え, compare-nat はどの CompareNat かがファイルごとに違う
+            if !path.ends_with("eval-ml2/q038.txt") && sys != System::CompareNat1 {
+                check(&back, sys).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            }
+        }
+    }
+}
+
+// 継続の値はフレームの配列として読み直せる
+#[test]
+fn round_trip_continuations() {
+    let d = parse_derivation("[{1 + _}] => {k = [{1 + _}] |- _ 3} >> {2 + _} evalto 4 by C-EvalArg ?").unwrap();
+    let json = parse_json(&export(&d, System::EvalContML4).to_string()).unwrap();
+    let form = json.get("derivation").unwrap().get("form").unwrap();
+    assert_eq!(form.get("kind"), Some(&Json::str("cont-ret")));
+    let mut ns = vec![];
+    names(&json, &mut ns);
+    assert_eq!(value_from_json(form.get("arg").unwrap(), &ns).unwrap().to_string(), "[{1 + _}]");
+    let frames = form.get("cont").unwrap().as_array().unwrap();
+    assert_eq!(frames.len(), 2);
+    assert!(frames[0].get("env").is_some() && frames[1].get("env").is_none());
+}
+
+#[test]
+fn round_trip_holes() {
+    let d = parse_derivation("3 + 4 evalto ? by E-Plus ?").unwrap();
+    let back = round_trip(&d, System::EvalML3);
+    assert!(back.hole);
+}
+
+#[test]
+fn round_trip_expressions() {
+    for text in [
+        "let rec f = fun x -> if x < 1 then 0 else x + f (x - 1) in f 3",
+        "match x with [] -> -1 | y :: _ -> y",
+        "fun x -> (fun y -> y) x :: []",
+        "let . = #1 * 2 in fun . -> #1 + #2",
+        "letcc k in 1 + k (2 < 3)",
+        "let x = ref 1 in x := !x + 1"
+    ] {
+        let tokens = tokenize(text.as_bytes()).unwrap();
+        let e = parse_values(&[], &tokens).unwrap().1;
+        let json = parse_json(&expr_json(&e).to_string()).unwrap();
+        let mut ns = vec![];
+        names(&json, &mut ns);
+        assert_eq!(expr_from_json(&json, &ns).unwrap().to_string(), e.to_string());
+    }
+}
+
+#[test]
+fn reject_other_versions() {
+    let d = derive(System::EvalML3, "", "1 + 2");
+    let text = export(&d, System::EvalML3).to_string()
+        .replacen(&format!("\"version\": {}", VERSION), "\"version\": 999", 1);
+    let error = import(&parse_json(&text).unwrap()).unwrap_err();
+    assert!(error.to_string().contains("unsupported version 999"), "{}", error);
+}
+
+#[test]
+fn reject_form_that_disagrees_with_judgment() {
+    let d = derive(System::EvalML3, "", "1 + 2");
+    let text = export(&d, System::EvalML3).to_string()
+        .replacen("\"judgment\": \"|- 1 + 2 evalto 3\"", "\"judgment\": \"|- 1 + 2 evalto 4\"", 1);
+    let error = import(&parse_json(&text).unwrap()).unwrap_err();
+    assert!(error.to_string().contains("root: form does not match"), "{}", error);
+}
+
+#[test]
+fn json_strings_and_numbers() {
+    let json = Json::object(vec![
+        ("s", Json::str("a \"quoted\" \\ line\nnext\t\u{1}")),
+        ("n", Json::Int(-42)),
+        ("empty", Json::Array(vec![])),
+        ("null", Json::Null)
+    ]);
+    assert_eq!(parse_json(&json.to_string()).unwrap(), json);
+    assert_eq!(parse_json(r#"{"u": "あ\/"}"#).unwrap(), Json::object(vec![("u", Json::str("あ/"))]));
+    assert!(parse_json("1.5").is_err());
+    assert!(parse_json("[1, 2").is_err());
+}